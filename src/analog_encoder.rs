@@ -0,0 +1,107 @@
+use crate::{analog_input::AnalogInput, analog_trigger::{AnalogTrigger, AnalogTriggerType}, counter::{Counter, TwoPulse}, dio::DigitalSource, error::{send_warning, HALResult}, power::get_user_voltage_5v};
+
+/// Absolute rotational sensor with a 0-5 V analog output, such as an MA3 or a continuous-turn
+/// potentiometer, tracked as a continuous angle.
+///
+/// As in WPILib, an analog trigger on the input fires whenever the output jumps across the
+/// rollover, and an up/down counter on the trigger's pulses counts whole turns in the FPGA,
+/// so no turns are missed no matter how rarely this is read.
+/// Readings are ratiometric to the 5 V rail.
+///
+/// The trigger is borrowed from the caller; [`AnalogEncoder::trigger_for`] makes one set up
+/// for the rollover.
+///
+/// ```ignore
+/// let trigger = AnalogEncoder::trigger_for(&input)?;
+/// let mut encoder = AnalogEncoder::new(&input, &trigger)?;
+/// ```
+#[derive(Debug)]
+pub struct AnalogEncoder<'a> {
+    counter: Counter<'a, TwoPulse>,
+    trigger: &'a AnalogTrigger<'a>,
+    input: &'a AnalogInput,
+    position_offset: f64,
+    distance_per_rotation: f64,
+    last_value: f64,
+}
+
+impl<'a> AnalogEncoder<'a> {
+    /// Read attempts before [`AnalogEncoder::get`] gives up on a consistent reading.
+    const READ_ATTEMPTS: usize = 10;
+
+    /// Makes an analog trigger on `input` that fires when the output jumps across the rollover.
+    pub fn trigger_for(input: &'a AnalogInput) -> HALResult<AnalogTrigger<'a>> {
+        let mut trigger = AnalogTrigger::initialize_analog(input)?;
+        trigger.set_limits_voltage(1.25, 3.75)?;
+        Ok(trigger)
+    }
+
+    /// * `trigger` - a trigger on `input`, as made by [`AnalogEncoder::trigger_for`]
+    pub fn new(input: &'a AnalogInput, trigger: &'a AnalogTrigger<'a>) -> HALResult<Self> {
+        let counter = Counter::two_pulse(
+            Some(DigitalSource::AnalogTrigger(trigger, AnalogTriggerType::kRisingPulse)),
+            Some(DigitalSource::AnalogTrigger(trigger, AnalogTriggerType::kFallingPulse)),
+        )?;
+        Ok(Self { counter, trigger, input, position_offset: 0.0, distance_per_rotation: 1.0, last_value: 0.0 })
+    }
+
+    /// The analog trigger detecting rollovers.
+    pub fn trigger(&self) -> &'a AnalogTrigger<'a> {
+        self.trigger
+    }
+
+    /// Position within one rotation, from 0 to 1, without the offset applied.
+    ///
+    /// Unlike [`crate::analog_potentiometer::AnalogPotentiometer`], this reads the instantaneous
+    /// voltage rather than the averaged one: averaging across a rollover would give positions
+    /// halfway round the circle that don't match the trigger's turn count.
+    pub fn get_absolute_position(&self) -> HALResult<f64> {
+        let rail = get_user_voltage_5v()?;
+        if rail <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok((self.input.get_voltage()? / rail).clamp(0.0, 1.0))
+    }
+
+    /// Total rotations since the last reset.
+    ///
+    /// The turn count and position are read twice and retried until they agree, so a rollover
+    /// between the two reads doesn't produce a one-turn glitch. If they never agree
+    /// (the shaft is spinning too fast), a warning is sent and the last good value is returned.
+    pub fn get(&mut self) -> HALResult<f64> {
+        for _ in 0..Self::READ_ATTEMPTS {
+            let turns = self.counter.get()?;
+            let position = self.get_absolute_position()?;
+            let turns2 = self.counter.get()?;
+            let position2 = self.get_absolute_position()?;
+            if turns == turns2 && (position - position2).abs() < 1e-5 {
+                self.last_value = turns as f64 + position - self.position_offset;
+                return Ok(self.last_value);
+            }
+        }
+        send_warning(1, c"Failed to read analog encoder, possible speed overrun. Returning last value.").ok();
+        Ok(self.last_value)
+    }
+
+    /// Distance since the last reset, in the units of the distance per rotation.
+    pub fn get_distance(&mut self) -> HALResult<f64> {
+        Ok(self.get()? * self.distance_per_rotation)
+    }
+
+    pub fn set_distance_per_rotation(&mut self, distance_per_rotation: f64) {
+        self.distance_per_rotation = distance_per_rotation;
+    }
+
+    /// Sets the absolute position, from 0 to 1, that reads as zero.
+    pub fn set_position_offset(&mut self, offset: f64) {
+        self.position_offset = offset.clamp(0.0, 1.0);
+    }
+
+    /// Makes the current position read as zero.
+    pub fn reset(&mut self) -> HALResult<()> {
+        self.counter.reset()?;
+        self.position_offset = self.get_absolute_position()?;
+        self.last_value = 0.0;
+        Ok(())
+    }
+}
@@ -1,18 +1,23 @@
-use std::ffi::CStr;
+use std::{ffi::CStr, fmt, sync::atomic::{AtomicUsize, Ordering}, time::Duration};
 
 use wpihal_sys::{HAL_CheckAnalogInputChannel, HAL_CheckAnalogModule, HAL_FreeAnalogInputPort, HAL_GetAnalogAverageBits, HAL_GetAnalogAverageValue, HAL_GetAnalogAverageVoltage, HAL_GetAnalogLSBWeight, HAL_GetAnalogOffset, HAL_GetAnalogOversampleBits, HAL_GetAnalogSampleRate, HAL_GetAnalogValue, HAL_GetAnalogValueToVolts, HAL_GetAnalogVoltage, HAL_GetAnalogVoltsToValue, HAL_InitializeAnalogInputPort, HAL_IsAccumulatorChannel, HAL_PortHandle, HAL_SetAnalogAverageBits, HAL_SetAnalogInputSimDevice, HAL_SetAnalogOversampleBits, HAL_SetAnalogSampleRate};
 
-use crate::{error::{allocation_location_ptr, HALResult}, hal_call, sim_device::SimDevice, Handle};
+use crate::{error::{allocation_location_ptr, send_warning, HALError, HALResult, PARAMETER_OUT_OF_RANGE}, hal_call, sim_device::SimDevice, Handle};
 
 /// Raw analog input handle 
 pub use wpihal_sys::HAL_AnalogInputHandle as AnalogInputHandle;
 
+/// Number of analog inputs currently open, so changes to the global sample rate can warn about them.
+static OPEN_CHANNELS: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct AnalogInput(AnalogInputHandle);
 
 impl AnalogInput {
     pub fn initialize(port: HAL_PortHandle, allocation_location: Option<&CStr>) -> HALResult<Self> {
-        Ok(Self(hal_call!(HAL_InitializeAnalogInputPort(port, allocation_location_ptr(allocation_location)))?))
+        let handle = hal_call!(HAL_InitializeAnalogInputPort(port, allocation_location_ptr(allocation_location)))?;
+        OPEN_CHANNELS.fetch_add(1, Ordering::Relaxed);
+        Ok(Self(handle))
     }
 
     pub fn is_accumulator_channel(&self) -> HALResult<bool> {
@@ -98,6 +103,7 @@ impl AnalogInput {
 impl Drop for AnalogInput {
     fn drop(&mut self) {
         unsafe { HAL_FreeAnalogInputPort(self.0); }
+        OPEN_CHANNELS.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
@@ -107,6 +113,182 @@ impl Handle<AnalogInputHandle> for AnalogInput {
     }
 
     unsafe fn from_raw_handle(handle: AnalogInputHandle) -> Self {
+        OPEN_CHANNELS.fetch_add(1, Ordering::Relaxed);
         Self(handle)
     }
-}
\ No newline at end of file
+}
+
+/// A voltage, in volts.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Voltage(pub f64);
+
+impl Voltage {
+    pub const fn volts(&self) -> f64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Voltage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} V", self.0)
+    }
+}
+
+/// Validated averaging, oversampling and sample rate settings for an [`AnalogInput`].
+///
+/// The FPGA samples every analog input round-robin at the global sample rate.
+/// Oversampling sums `2^oversample_bits` samples into one (more resolution), and averaging
+/// then averages `2^average_bits` of those (less noise); both cost latency.
+///
+/// The settings are checked when they are applied. Applying a config doesn't borrow the input,
+/// so it can still be shared with other readers afterwards.
+///
+/// ```ignore
+/// AnalogInputConfig::new().average_bits(4).oversample_bits(2).apply(&mut input)?;
+/// let reader = CalibratedAnalogInput::new(&input)?;
+/// let volts = reader.get_average_voltage()?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalogInputConfig {
+    average_bits: u8,
+    oversample_bits: u8,
+    sample_rate: Option<f64>,
+}
+
+impl Default for AnalogInputConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnalogInputConfig {
+    /// Highest per-channel sample rate, in samples per second: the roboRIO's ADC is rated for
+    /// 500 kS/s aggregate, shared by its 8 channels.
+    pub const MAX_SAMPLE_RATE: f64 = 62_500.0;
+    /// Default per-channel sample rate, in samples per second.
+    pub const DEFAULT_SAMPLE_RATE: f64 = 50_000.0;
+
+    /// The HAL's defaults: 7 average bits, no oversampling, and the global sample rate left alone.
+    pub const fn new() -> Self {
+        Self { average_bits: 7, oversample_bits: 0, sample_rate: None }
+    }
+
+    pub fn average_bits(mut self, bits: u8) -> Self {
+        self.average_bits = bits;
+        self
+    }
+
+    pub fn oversample_bits(mut self, bits: u8) -> Self {
+        self.oversample_bits = bits;
+        self
+    }
+
+    /// Sets the global per-channel sample rate, which affects every analog input.
+    pub fn sample_rate(mut self, samples_per_second: f64) -> Self {
+        self.sample_rate = Some(samples_per_second);
+        self
+    }
+
+    /// Applies the settings to `input`.
+    ///
+    /// Fails with `PARAMETER_OUT_OF_RANGE` if the sample rate is above [`Self::MAX_SAMPLE_RATE`],
+    /// or if the FPGA doesn't hold the requested bit counts (they are read back after being
+    /// written, so the hardware's own field widths are the limit).
+    /// If this changes the global sample rate while other analog inputs are open,
+    /// a warning is sent, since their timing changes too.
+    pub fn apply(&self, input: &mut AnalogInput) -> HALResult<()> {
+        if let Some(rate) = self.sample_rate {
+            if !(rate > 0.0 && rate <= Self::MAX_SAMPLE_RATE) {
+                return Err(HALError(PARAMETER_OUT_OF_RANGE));
+            }
+            let current = AnalogInput::get_sample_rate()?;
+            if (current - rate).abs() > f64::EPSILON * rate {
+                let others = OPEN_CHANNELS.load(Ordering::Relaxed).saturating_sub(1);
+                if others > 0 {
+                    let msg = format!("Changing the analog sample rate from {current} to {rate} S/s also affects {others} other open analog input(s)");
+                    if let Ok(msg) = std::ffi::CString::new(msg) {
+                        send_warning(1, &msg).ok();
+                    }
+                }
+                AnalogInput::set_sample_rate(rate)?;
+            }
+        }
+
+        input.set_average_bits(self.average_bits as i32)?;
+        input.set_oversample_bits(self.oversample_bits as i32)?;
+        if input.get_average_bits()? != self.average_bits as i32 || input.get_oversample_bits()? != self.oversample_bits as i32 {
+            return Err(HALError(PARAMETER_OUT_OF_RANGE));
+        }
+        Ok(())
+    }
+}
+
+/// Per-channel timing, from [`CalibratedAnalogInput::timing`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalogTiming {
+    /// Global per-channel sample rate, in samples per second.
+    pub sample_rate: f64,
+    /// Rate at which the averaged value updates, in samples per second.
+    pub averaged_rate: f64,
+    /// Time spanned by one averaged reading.
+    pub averaged_latency: Duration,
+}
+
+/// An [`AnalogInput`] with its calibration and bit counts cached, so conversions to volts are
+/// done in Rust.
+///
+/// The cache is taken when this is made, so make it after applying an [`AnalogInputConfig`].
+#[derive(Debug)]
+pub struct CalibratedAnalogInput<'a> {
+    input: &'a AnalogInput,
+    lsb_weight: i32,
+    offset: i32,
+    average_bits: u8,
+    oversample_bits: u8,
+}
+
+impl<'a> CalibratedAnalogInput<'a> {
+    pub fn new(input: &'a AnalogInput) -> HALResult<Self> {
+        Ok(Self {
+            input,
+            lsb_weight: input.get_lsb_weight()?,
+            offset: input.get_offset()?,
+            // clamped so the shifts in the conversions stay in range
+            average_bits: input.get_average_bits()?.clamp(0, 15) as u8,
+            oversample_bits: input.get_oversample_bits()?.clamp(0, 15) as u8,
+        })
+    }
+
+    pub fn input(&self) -> &'a AnalogInput {
+        self.input
+    }
+
+    /// Converts a raw (not averaged) value to volts.
+    pub fn raw_to_voltage(&self, raw: i32) -> Voltage {
+        Voltage(self.lsb_weight as f64 * 1.0e-9 * raw as f64 - self.offset as f64 * 1.0e-9)
+    }
+
+    /// Converts an averaged (and oversampled) value to volts.
+    pub fn average_to_voltage(&self, value: i32) -> Voltage {
+        let raw = value as f64 / (1u32 << self.oversample_bits) as f64;
+        Voltage(self.lsb_weight as f64 * 1.0e-9 * raw - self.offset as f64 * 1.0e-9)
+    }
+
+    pub fn get_voltage(&self) -> HALResult<Voltage> {
+        Ok(self.raw_to_voltage(self.input.get_value()?))
+    }
+
+    pub fn get_average_voltage(&self) -> HALResult<Voltage> {
+        Ok(self.average_to_voltage(self.input.get_average_value()?))
+    }
+
+    pub fn timing(&self) -> HALResult<AnalogTiming> {
+        let sample_rate = AnalogInput::get_sample_rate()?;
+        let averaged_rate = sample_rate / (1u32 << (self.average_bits + self.oversample_bits)) as f64;
+        Ok(AnalogTiming {
+            sample_rate,
+            averaged_rate,
+            averaged_latency: if averaged_rate > 0.0 { Duration::from_secs_f64(1.0 / averaged_rate) } else { Duration::ZERO },
+        })
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{analog_input::AnalogInput, error::HALResult, power::get_user_voltage_5v};
+
+/// Potentiometer (or any other ratiometric 0-5 V sensor) on an analog input.
+///
+/// Readings are taken as a fraction of the 5 V rail rather than of a fixed 5 V,
+/// so sag on the rail doesn't show up as motion.
+#[derive(Debug)]
+pub struct AnalogPotentiometer<'a> {
+    input: &'a AnalogInput,
+    full_range: f64,
+    offset: f64,
+}
+
+impl<'a> AnalogPotentiometer<'a> {
+    /// * `full_range` - value at the top of the potentiometer's travel, relative to the bottom,
+    ///   e.g. 270 for a 270 degree potentiometer read in degrees
+    /// * `offset` - value at the bottom of the travel
+    pub fn new(input: &'a AnalogInput, full_range: f64, offset: f64) -> Self {
+        Self { input, full_range, offset }
+    }
+
+    /// Position in the units of `full_range`.
+    pub fn get(&self) -> HALResult<f64> {
+        Ok(self.get_ratio()? * self.full_range + self.offset)
+    }
+
+    /// Input voltage as a fraction of the 5 V rail, from 0 to 1.
+    pub fn get_ratio(&self) -> HALResult<f64> {
+        let rail = get_user_voltage_5v()?;
+        if rail <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok((self.input.get_average_voltage()? / rail).clamp(0.0, 1.0))
+    }
+
+    pub fn set_full_range(&mut self, full_range: f64) {
+        self.full_range = full_range;
+    }
+
+    pub fn set_offset(&mut self, offset: f64) {
+        self.offset = offset;
+    }
+}
@@ -0,0 +1,150 @@
+use std::{pin::Pin, sync::{Arc, Mutex, PoisonError}, task::{Context, Poll, Waker}, thread::JoinHandle, time::Duration};
+
+use futures_core::Stream;
+
+use crate::{error::{thread_spawn_error, HALResult}, get_fpga_time, notifier::Notifier, time::{next_deadline, FpgaInstant}};
+
+#[derive(Default)]
+struct State {
+    fired: Option<FpgaInstant>,
+    stopped: bool,
+    waker: Option<Waker>,
+}
+
+/// A HAL notifier that can be awaited.
+///
+/// A small dedicated thread blocks in [`Notifier::wait_for_alarm`] and wakes whichever task is
+/// waiting on [`AsyncNotifier::tick`], so this works with any executor.
+/// Dropping it stops the underlying notifier and joins the waiter thread.
+pub struct AsyncNotifier {
+    notifier: Arc<Notifier>,
+    state: Arc<Mutex<State>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncNotifier {
+    pub fn new() -> HALResult<Self> {
+        let mut notifier = Notifier::initialize()?;
+        notifier.set_name(c"AsyncNotifier")?;
+        let notifier = Arc::new(notifier);
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let thread = {
+            let notifier = notifier.clone();
+            let state = state.clone();
+            std::thread::Builder::new().name("AsyncNotifier".to_string()).spawn(move || loop {
+                let time = notifier.wait_for_alarm().unwrap_or(None);
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                match time {
+                    Some(time) => state.fired = Some(time),
+                    None => state.stopped = true,
+                }
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+                if state.stopped {
+                    break;
+                }
+            }).map_err(|e| thread_spawn_error("AsyncNotifier", e))?
+        };
+
+        Ok(Self { notifier, state, thread: Some(thread) })
+    }
+
+    /// Creates a stream that yields every `period`, starting one period from now.
+    pub fn interval(period: Duration) -> HALResult<Interval> {
+        let notifier = Self::new()?;
+        let period = period.max(Duration::from_micros(1));
+        let next = get_fpga_time()? + period;
+        notifier.update_alarm(next)?;
+        Ok(Interval { notifier, period, next })
+    }
+
+    /// Sets the alarm to an absolute FPGA time, discarding any unconsumed previous alarm.
+    pub fn update_alarm(&self, trigger_time: FpgaInstant) -> HALResult<()> {
+        self.lock().fired = None;
+        self.notifier.update_alarm(trigger_time)
+    }
+
+    pub fn cancel_alarm(&self) -> HALResult<()> {
+        self.lock().fired = None;
+        self.notifier.cancel_alarm()
+    }
+
+    /// Stops the notifier. Pending and future ticks resolve to `None`.
+    pub fn stop(&self) -> HALResult<()> {
+        self.notifier.stop()
+    }
+
+    /// Resolves with the FPGA time the alarm fired at, or `None` once the notifier is stopped.
+    pub async fn tick(&self) -> Option<FpgaInstant> {
+        core::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    pub fn poll_tick(&self, cx: &mut Context<'_>) -> Poll<Option<FpgaInstant>> {
+        let mut state = self.lock();
+        if let Some(time) = state.fired.take() {
+            Poll::Ready(Some(time))
+        } else if state.stopped {
+            Poll::Ready(None)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for AsyncNotifier {
+    fn drop(&mut self) {
+        self.notifier.stop().ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Periodic stream created by [`AsyncNotifier::interval`].
+///
+/// Alarms are scheduled at absolute times, so the interval doesn't drift; if the consumer
+/// falls behind, missed ticks are skipped rather than delivered in a burst.
+pub struct Interval {
+    notifier: AsyncNotifier,
+    period: Duration,
+    next: FpgaInstant,
+}
+
+impl Interval {
+    /// Resolves with the FPGA time of the next tick, or `None` once stopped.
+    pub async fn tick(&mut self) -> Option<FpgaInstant> {
+        core::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Option<FpgaInstant>> {
+        let time = match self.notifier.poll_tick(cx) {
+            Poll::Ready(Some(time)) => time,
+            other => return other,
+        };
+
+        self.next = next_deadline(self.next, self.period, time).0;
+        if self.notifier.update_alarm(self.next).is_err() {
+            self.notifier.stop().ok();
+        }
+        Poll::Ready(Some(time))
+    }
+
+    pub fn stop(&self) -> HALResult<()> {
+        self.notifier.stop()
+    }
+}
+
+impl Stream for Interval {
+    type Item = FpgaInstant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<FpgaInstant>> {
+        self.get_mut().poll_tick(cx)
+    }
+}
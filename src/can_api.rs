@@ -1,6 +1,6 @@
 use wpihal_sys::{HAL_CANDeviceType, HAL_CANHandle, HAL_CANManufacturer, HAL_CleanCAN, HAL_GetCANPacketBaseTime, HAL_InitializeCAN, HAL_ReadCANPacketNew, HAL_ReadCANPacketTimeout, HAL_StopCANPacketRepeating, HAL_WriteCANPacket, HAL_WriteCANPacketRepeating, HAL_WriteCANRTRFrame};
 
-use crate::{can::CANStreamMessage, error::HALResult, hal_call};
+use crate::{can::CANStreamMessage, error::HALResult, hal_call, time::FpgaInstant};
 
 pub fn get_can_packet_base_time() -> u32 {
     unsafe { HAL_GetCANPacketBaseTime() }
@@ -24,6 +24,7 @@ impl CANPacket {
     pub fn new(data: &[u8], api_id: u16) -> Self {
         Self::new_with_timestamp(data, api_id, 0)
     }
+    /// `timestamp` is in the CAN driver's raw milliseconds, as returned by [`CANPacket::raw_timestamp_ms`].
     pub fn new_with_timestamp(data: &[u8], api_id: u16, timestamp: u64) -> Self {
         let length = data.len().min(8);
         let mut data_buf =  [0u8; 8];
@@ -48,8 +49,14 @@ impl CANPacket {
         self.api_id
     }
 
-    pub fn timestamp(&self) -> u64 {
-        self.timestamp
+    /// When the packet was received, converted from the CAN driver's millisecond counter.
+    pub fn timestamp(&self) -> HALResult<FpgaInstant> {
+        FpgaInstant::from_can_timestamp(self.raw_timestamp_ms())
+    }
+
+    /// The CAN driver's raw 32-bit millisecond timestamp, see [`CANPacket::timestamp`].
+    pub fn raw_timestamp_ms(&self) -> u32 {
+        self.timestamp as u32
     }
 }
 
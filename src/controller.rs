@@ -0,0 +1,289 @@
+use core::marker::PhantomData;
+
+use crate::{driver_station::{get_joystick_axes, get_joystick_descriptor, get_joystick_buttons, get_joystick_povs, set_joystick_outputs, ControllerDescriptor, HIDType, JoystickAxes, JoystickButtons, JoystickDescriptor, JoystickPOVs}, error::HALResult};
+
+/// A fixed axis/button layout for a particular kind of controller.
+///
+/// The driver station only hands us numbered axes and buttons, so a layout also
+/// decides from the [`ControllerDescriptor`] whether the plugged-in device is
+/// one it actually knows how to read.
+pub trait ControllerLayout {
+    type Axis: Copy + Into<usize>;
+    type Button: Copy + Into<u32>;
+
+    /// Returns true if the numbering of this layout applies to the described device.
+    fn matches(descriptor: &ControllerDescriptor) -> bool;
+}
+
+/// A driver station controller read through a [`ControllerLayout`].
+///
+/// Call [`Controller::update`] once per loop (after [`crate::driver_station::refresh_ds_data`]).
+/// If the device in the slot doesn't match the layout, every axis reads 0,
+/// every button reads released, and the POV reads centered.
+pub struct Controller<L: ControllerLayout> {
+    port: i32,
+    /// Raw descriptor the match below was made from.
+    raw_descriptor: Option<JoystickDescriptor>,
+    descriptor: Option<ControllerDescriptor>,
+    axes: JoystickAxes,
+    buttons: JoystickButtons,
+    povs: JoystickPOVs,
+    _layout: PhantomData<L>,
+}
+
+impl<L: ControllerLayout> Controller<L> {
+    pub fn new(port: i32) -> Self {
+        Self {
+            port,
+            raw_descriptor: None,
+            descriptor: None,
+            axes: Default::default(),
+            buttons: Default::default(),
+            povs: Default::default(),
+            _layout: PhantomData,
+        }
+    }
+
+    pub fn port(&self) -> i32 {
+        self.port
+    }
+
+    /// Re-checks the descriptor and snapshots axes, buttons and POVs.
+    ///
+    /// The layout is only matched again when the device in the slot changes.
+    /// Returns whether the layout mapping is trusted for this cycle.
+    pub fn update(&mut self) -> HALResult<bool> {
+        let raw = get_joystick_descriptor(self.port)?;
+        if !self.raw_descriptor.as_ref().is_some_and(|last| same_device(last, &raw)) {
+            let descriptor = ControllerDescriptor::from(&raw);
+            self.descriptor = L::matches(&descriptor).then_some(descriptor);
+            self.raw_descriptor = Some(raw);
+        }
+
+        if self.is_mapped() {
+            self.axes = get_joystick_axes(self.port)?;
+            self.buttons = get_joystick_buttons(self.port)?;
+            self.povs = get_joystick_povs(self.port)?;
+        } else {
+            self.axes = Default::default();
+            self.buttons = Default::default();
+            self.povs = Default::default();
+        }
+        Ok(self.is_mapped())
+    }
+
+    /// Whether the last [`Controller::update`] found a device matching the layout.
+    pub fn is_mapped(&self) -> bool {
+        self.descriptor.is_some()
+    }
+
+    /// The descriptor from the last successful match, if any.
+    pub fn descriptor(&self) -> Option<&ControllerDescriptor> {
+        self.descriptor.as_ref()
+    }
+
+    /// Axis value in [-1, 1].
+    pub fn axis(&self, axis: L::Axis) -> f64 {
+        let index = axis.into();
+        if index < (self.axes.count.max(0) as usize).min(self.axes.axes.len()) {
+            self.axes.axes[index] as f64
+        } else {
+            0.0
+        }
+    }
+
+    pub fn button(&self, button: L::Button) -> bool {
+        let number = button.into();
+        number >= 1 && number <= (self.buttons.count as u32).min(32) && self.buttons.buttons & (1 << (number - 1)) != 0
+    }
+
+    /// POV hat angle in degrees, or `None` if centered.
+    pub fn pov(&self) -> Option<i16> {
+        if self.povs.count > 0 && self.povs.povs[0] >= 0 {
+            Some(self.povs.povs[0])
+        } else {
+            None
+        }
+    }
+
+    /// Sets the rumble motors, each in [0, 1].
+    pub fn set_rumble(&mut self, left: f64, right: f64) -> HALResult<()> {
+        let scale = |v: f64| (v.clamp(0.0, 1.0) * u16::MAX as f64) as u16;
+        set_joystick_outputs(self.port, 0, scale(left), scale(right))
+    }
+}
+
+/// Whether two raw descriptors describe the same device, without building either one's name.
+fn same_device(a: &JoystickDescriptor, b: &JoystickDescriptor) -> bool {
+    a.type_ == b.type_
+        && a.isXbox == b.isXbox
+        && a.name == b.name
+        && a.axisCount == b.axisCount
+        && a.axisTypes == b.axisTypes
+        && a.buttonCount == b.buttonCount
+        && a.povCount == b.povCount
+}
+
+#[repr(usize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XboxAxis {
+    LeftX = 0,
+    LeftY = 1,
+    LeftTrigger = 2,
+    RightTrigger = 3,
+    RightX = 4,
+    RightY = 5,
+}
+
+impl From<XboxAxis> for usize {
+    fn from(value: XboxAxis) -> Self {
+        value as usize
+    }
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XboxButton {
+    A = 1,
+    B = 2,
+    X = 3,
+    Y = 4,
+    LeftBumper = 5,
+    RightBumper = 6,
+    Back = 7,
+    Start = 8,
+    LeftStick = 9,
+    RightStick = 10,
+}
+
+impl From<XboxButton> for u32 {
+    fn from(value: XboxButton) -> Self {
+        value as u32
+    }
+}
+
+/// XInput gamepad layout.
+pub struct XboxLayout;
+
+impl ControllerLayout for XboxLayout {
+    type Axis = XboxAxis;
+    type Button = XboxButton;
+
+    fn matches(descriptor: &ControllerDescriptor) -> bool {
+        (descriptor.is_xbox || descriptor.hid_type == HIDType::XInputGamepad)
+            && descriptor.axis_count() >= 6
+            && descriptor.button_count >= 10
+    }
+}
+
+pub type XboxController = Controller<XboxLayout>;
+
+/// Axes shared by the DualShock 4 and DualSense.
+#[repr(usize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PSAxis {
+    LeftX = 0,
+    LeftY = 1,
+    RightX = 2,
+    L2 = 3,
+    R2 = 4,
+    RightY = 5,
+}
+
+impl From<PSAxis> for usize {
+    fn from(value: PSAxis) -> Self {
+        value as usize
+    }
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PS4Button {
+    Square = 1,
+    Cross = 2,
+    Circle = 3,
+    Triangle = 4,
+    L1 = 5,
+    R1 = 6,
+    L2 = 7,
+    R2 = 8,
+    Share = 9,
+    Options = 10,
+    L3 = 11,
+    R3 = 12,
+    PS = 13,
+    Touchpad = 14,
+}
+
+impl From<PS4Button> for u32 {
+    fn from(value: PS4Button) -> Self {
+        value as u32
+    }
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PS5Button {
+    Square = 1,
+    Cross = 2,
+    Circle = 3,
+    Triangle = 4,
+    L1 = 5,
+    R1 = 6,
+    L2 = 7,
+    R2 = 8,
+    Create = 9,
+    Options = 10,
+    L3 = 11,
+    R3 = 12,
+    PS = 13,
+    Touchpad = 14,
+}
+
+impl From<PS5Button> for u32 {
+    fn from(value: PS5Button) -> Self {
+        value as u32
+    }
+}
+
+/// Sony controllers show up as plain HID gamepads with the same shape, so the DualShock 4 and
+/// DualSense are told apart by name: Windows names the DualSense "DualSense Wireless Controller"
+/// and the DualShock 4 just "Wireless Controller".
+fn matches_playstation(descriptor: &ControllerDescriptor) -> bool {
+    !descriptor.is_xbox
+        && descriptor.hid_type == HIDType::HIDGamepad
+        && descriptor.axis_count() >= 6
+        && descriptor.button_count >= 14
+}
+
+fn is_dualsense(descriptor: &ControllerDescriptor) -> bool {
+    descriptor.name.contains("DualSense")
+}
+
+/// DualShock 4 layout.
+pub struct PS4Layout;
+
+impl ControllerLayout for PS4Layout {
+    type Axis = PSAxis;
+    type Button = PS4Button;
+
+    fn matches(descriptor: &ControllerDescriptor) -> bool {
+        matches_playstation(descriptor) && !is_dualsense(descriptor)
+    }
+}
+
+pub type PS4Controller = Controller<PS4Layout>;
+
+/// DualSense layout.
+pub struct PS5Layout;
+
+impl ControllerLayout for PS5Layout {
+    type Axis = PSAxis;
+    type Button = PS5Button;
+
+    fn matches(descriptor: &ControllerDescriptor) -> bool {
+        matches_playstation(descriptor) && is_dualsense(descriptor)
+    }
+}
+
+pub type PS5Controller = Controller<PS5Layout>;
@@ -1,118 +1,239 @@
-use wpihal_sys::{HAL_ClearCounterDownSource, HAL_ClearCounterUpSource, HAL_CounterHandle, HAL_Counter_Mode, HAL_FreeCounter, HAL_GetCounter, HAL_GetCounterDirection, HAL_GetCounterPeriod, HAL_GetCounterSamplesToAverage, HAL_GetCounterStopped, HAL_Handle, HAL_InitializeCounter, HAL_ResetCounter, HAL_SetCounterAverageSize, HAL_SetCounterDownSource, HAL_SetCounterDownSourceEdge, HAL_SetCounterExternalDirectionMode, HAL_SetCounterMaxPeriod, HAL_SetCounterPulseLengthMode, HAL_SetCounterReverseDirection, HAL_SetCounterSamplesToAverage, HAL_SetCounterSemiPeriodMode, HAL_SetCounterUpDownMode, HAL_SetCounterUpSource, HAL_SetCounterUpSourceEdge, HAL_SetCounterUpdateWhenEmpty};
+use core::marker::PhantomData;
 
-use crate::{analog_trigger::AnalogTriggerType, error::HALResult, hal_call};
+use wpihal_sys::{HAL_ClearCounterDownSource, HAL_ClearCounterUpSource, HAL_CounterHandle, HAL_Counter_Mode, HAL_FreeCounter, HAL_GetCounter, HAL_GetCounterDirection, HAL_GetCounterPeriod, HAL_GetCounterSamplesToAverage, HAL_GetCounterStopped, HAL_InitializeCounter, HAL_ResetCounter, HAL_SetCounterAverageSize, HAL_SetCounterDownSource, HAL_SetCounterDownSourceEdge, HAL_SetCounterExternalDirectionMode, HAL_SetCounterMaxPeriod, HAL_SetCounterPulseLengthMode, HAL_SetCounterReverseDirection, HAL_SetCounterSamplesToAverage, HAL_SetCounterSemiPeriodMode, HAL_SetCounterUpDownMode, HAL_SetCounterUpSource, HAL_SetCounterUpSourceEdge, HAL_SetCounterUpdateWhenEmpty};
+
+use crate::{dio::DigitalSource, error::HALResult, hal_call, Handle};
 
 
 pub type CounterMode = HAL_Counter_Mode;
 
-pub struct Counter {
+mod private {
+    pub trait Sealed {}
+}
+
+/// Type-level counter mode; see [`TwoPulse`], [`ExternalDirection`], [`SemiPeriod`] and [`PulseLength`].
+pub trait Mode: private::Sealed {
+    const MODE: CounterMode;
+}
+
+/// Counts up on one source and down on another.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TwoPulse;
+/// Counts pulses on one source, with a second source giving the direction.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExternalDirection;
+/// Measures the length of the high (or low) part of a signal, read through [`Counter::get_period`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct SemiPeriod;
+/// Counts up on short pulses and down on long ones, as used by gear tooth sensors.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PulseLength;
+
+impl private::Sealed for TwoPulse {}
+impl private::Sealed for ExternalDirection {}
+impl private::Sealed for SemiPeriod {}
+impl private::Sealed for PulseLength {}
+
+impl Mode for TwoPulse {
+    const MODE: CounterMode = HAL_Counter_Mode::kTwoPulse;
+}
+impl Mode for ExternalDirection {
+    const MODE: CounterMode = HAL_Counter_Mode::kExternalDirection;
+}
+impl Mode for SemiPeriod {
+    const MODE: CounterMode = HAL_Counter_Mode::kSemiperiod;
+}
+impl Mode for PulseLength {
+    const MODE: CounterMode = HAL_Counter_Mode::kPulseLength;
+}
+
+/// FPGA counter, borrowing its sources like [`crate::encoder::Encoder`].
+///
+/// The mode is part of the type, so only the settings that mean something in that mode are available.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Counter<'a, M: Mode> {
     handle: HAL_CounterHandle,
-    index: i32
+    index: i32,
+    up_source: Option<DigitalSource<'a>>,
+    down_source: Option<DigitalSource<'a>>,
+    _mode: PhantomData<M>,
 }
 
-impl Counter {
-    pub fn initialize(mode: HAL_Counter_Mode) -> HALResult<Self> {
+impl<'a, M: Mode> Counter<'a, M> {
+    fn initialize() -> HALResult<Self> {
         let mut index: i32 = 0;
-        let handle = hal_call!(HAL_InitializeCounter(mode, &mut index))?;
-        Ok(Self { handle, index })
+        let handle = hal_call!(HAL_InitializeCounter(M::MODE, &mut index))?;
+        Ok(Self { handle, index, up_source: None, down_source: None, _mode: PhantomData })
+    }
+
+    fn set_up_source(&mut self, source: DigitalSource<'a>, rising_edge: bool, falling_edge: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterUpSource(self.handle, source.raw_handle(), source.analog_trigger_type()))?;
+        self.up_source = Some(source);
+        hal_call!(HAL_SetCounterUpSourceEdge(self.handle, rising_edge as i32, falling_edge as i32))
+    }
+
+    fn set_down_source(&mut self, source: DigitalSource<'a>, rising_edge: bool, falling_edge: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterDownSource(self.handle, source.raw_handle(), source.analog_trigger_type()))?;
+        self.down_source = Some(source);
+        hal_call!(HAL_SetCounterDownSourceEdge(self.handle, rising_edge as i32, falling_edge as i32))
     }
 
+    /// FPGA index of the counter, or -1 if it was made with [`Handle::from_raw_handle`].
     pub fn index(&self) -> i32 {
         self.index
     }
 
-    pub fn set_average_size(&mut self, size: i32) -> HALResult<()> {
-        hal_call!(HAL_SetCounterAverageSize(self.handle, size))
+    pub fn reset(&mut self) -> HALResult<()> {
+        hal_call!(HAL_ResetCounter(self.handle))
     }
 
-    pub fn set_up_source(&mut self, digital_source_handle: HAL_Handle, analog_trigger_type: AnalogTriggerType) -> HALResult<()> {
-        hal_call!(HAL_SetCounterUpSource(self.handle, digital_source_handle, analog_trigger_type))
+    pub fn get(&self) -> HALResult<i32> {
+        hal_call!(HAL_GetCounter(self.handle))
     }
 
-    pub fn set_up_source_edge(&mut self, rising_edge: bool, falling_edge: bool) -> HALResult<()> {
-        hal_call!(HAL_SetCounterUpSourceEdge(self.handle, rising_edge as i32, falling_edge as i32))
+    /// Time between the last two counted edges, in seconds.
+    pub fn get_period(&self) -> HALResult<f64> {
+        hal_call!(HAL_GetCounterPeriod(self.handle))
     }
 
-    pub fn clear_up_source(&mut self) -> HALResult<()> {
-        hal_call!(HAL_ClearCounterUpSource(self.handle))
+    pub fn set_max_period(&mut self, max_period: f64) -> HALResult<()> {
+        hal_call!(HAL_SetCounterMaxPeriod(self.handle, max_period))
     }
 
-    pub fn set_down_source(&mut self, digital_source_handle: HAL_Handle, analog_trigger_type: AnalogTriggerType) -> HALResult<()> {
-        hal_call!(HAL_SetCounterDownSource(self.handle, digital_source_handle, analog_trigger_type))
+    pub fn set_update_when_empty(&mut self, enabled: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterUpdateWhenEmpty(self.handle, enabled as i32))
     }
 
-    pub fn set_down_source_edge(&mut self, rising_edge: bool, falling_edge: bool) -> HALResult<()> {
-        hal_call!(HAL_SetCounterDownSourceEdge(self.handle, rising_edge as i32, falling_edge as i32))
+    pub fn get_stopped(&self) -> HALResult<bool> {
+        Ok(hal_call!(HAL_GetCounterStopped(self.handle))? != 0)
     }
 
-    pub fn clear_down_source(&mut self) -> HALResult<()> {
-        hal_call!(HAL_ClearCounterDownSource(self.handle))
+    pub fn get_direction(&self) -> HALResult<bool> {
+        Ok(hal_call!(HAL_GetCounterDirection(self.handle))? != 0)
     }
 
-    pub fn set_up_down_mode(&mut self) -> HALResult<()> {
-        hal_call!(HAL_SetCounterUpDownMode(self.handle))
+    pub fn get_samples_to_average(&self) -> HALResult<i32> {
+        hal_call!(HAL_GetCounterSamplesToAverage(self.handle))
     }
 
-    pub fn set_external_direction_mode(&mut self) -> HALResult<()> {
-        hal_call!(HAL_SetCounterExternalDirectionMode(self.handle))
+    pub fn set_samples_to_average(&mut self, samples_to_average: i32) -> HALResult<()> {
+        hal_call!(HAL_SetCounterSamplesToAverage(self.handle, samples_to_average))
     }
 
-    pub fn set_semi_period_mode(&mut self, high_semi_period: bool) -> HALResult<()> {
-        hal_call!(HAL_SetCounterSemiPeriodMode(self.handle, high_semi_period as i32))
+    pub fn set_average_size(&mut self, size: i32) -> HALResult<()> {
+        hal_call!(HAL_SetCounterAverageSize(self.handle, size))
     }
+}
 
-    pub fn set_pulse_length_mode(&mut self, threshold: f64) -> HALResult<()> {
-        hal_call!(HAL_SetCounterPulseLengthMode(self.handle, threshold))
+impl<'a> Counter<'a, TwoPulse> {
+    /// Counts rising edges on `up` and `down`. Either can be left out.
+    pub fn two_pulse(up: Option<DigitalSource<'a>>, down: Option<DigitalSource<'a>>) -> HALResult<Self> {
+        let mut counter = Self::initialize()?;
+        if let Some(up) = up {
+            counter.set_up_source(up, true, false)?;
+        }
+        if let Some(down) = down {
+            counter.set_down_source(down, true, false)?;
+        }
+        hal_call!(HAL_SetCounterUpDownMode(counter.handle))?;
+        counter.reset()?;
+        Ok(counter)
+    }
+
+    pub fn set_up_edges(&mut self, rising_edge: bool, falling_edge: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterUpSourceEdge(self.handle, rising_edge as i32, falling_edge as i32))
     }
 
-    pub fn get_samples_to_average(&self) -> HALResult<i32> {
-        hal_call!(HAL_GetCounterSamplesToAverage(self.handle))
+    pub fn set_down_edges(&mut self, rising_edge: bool, falling_edge: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterDownSourceEdge(self.handle, rising_edge as i32, falling_edge as i32))
     }
 
-    pub fn set_samples_to_average(&mut self, samples_to_average: i32) -> HALResult<()> {
-        hal_call!(HAL_SetCounterSamplesToAverage(self.handle, samples_to_average))
+    /// Stops counting up, releasing the up source.
+    pub fn clear_up_source(&mut self) -> HALResult<()> {
+        hal_call!(HAL_ClearCounterUpSource(self.handle))?;
+        self.up_source = None;
+        Ok(())
     }
 
-    pub fn reset(&mut self) -> HALResult<()> {
-        hal_call!(HAL_ResetCounter(self.handle))
+    /// Stops counting down, releasing the down source.
+    pub fn clear_down_source(&mut self) -> HALResult<()> {
+        hal_call!(HAL_ClearCounterDownSource(self.handle))?;
+        self.down_source = None;
+        Ok(())
     }
+}
 
-    pub fn get(&self) -> HALResult<i32> {
-        hal_call!(HAL_GetCounter(self.handle))
+impl<'a> Counter<'a, ExternalDirection> {
+    /// Counts rising edges on `count`, up while `direction` is low and down while it is high.
+    pub fn external_direction(count: DigitalSource<'a>, direction: DigitalSource<'a>) -> HALResult<Self> {
+        let mut counter = Self::initialize()?;
+        counter.set_up_source(count, true, false)?;
+        // In external direction mode the down source edges select how the direction input is
+        // sampled rather than what is counted. WPILib's Counter(EncodingType, ...) uses
+        // (inverted, true), so this matches it for a non-inverted counter rather than using the
+        // HAL's (true, false) default; use set_reverse_direction to invert.
+        counter.set_down_source(direction, false, true)?;
+        hal_call!(HAL_SetCounterExternalDirectionMode(counter.handle))?;
+        counter.reset()?;
+        Ok(counter)
+    }
+
+    pub fn set_edges(&mut self, rising_edge: bool, falling_edge: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterUpSourceEdge(self.handle, rising_edge as i32, falling_edge as i32))
     }
 
-    pub fn get_period(&self) -> HALResult<f64> {
-        hal_call!(HAL_GetCounterPeriod(self.handle))
+    pub fn set_reverse_direction(&mut self, reverse_direction: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterReverseDirection(self.handle, reverse_direction as i32))
     }
+}
 
-    pub fn set_max_period(&mut self, max_period: f64) -> HALResult<()> {
-        hal_call!(HAL_SetCounterMaxPeriod(self.handle, max_period))
+impl<'a> Counter<'a, SemiPeriod> {
+    /// Measures how long `source` stays high (or low, if `high_semi_period` is false).
+    pub fn semi_period(source: DigitalSource<'a>, high_semi_period: bool) -> HALResult<Self> {
+        let mut counter = Self::initialize()?;
+        counter.set_up_source(source, true, true)?;
+        counter.set_high_semi_period(high_semi_period)?;
+        counter.set_samples_to_average(1)?;
+        counter.reset()?;
+        Ok(counter)
     }
 
-    pub fn set_update_when_empty(&mut self, enabled: bool) -> HALResult<()> {
-        hal_call!(HAL_SetCounterUpdateWhenEmpty(self.handle, enabled as i32))
+    pub fn set_high_semi_period(&mut self, high_semi_period: bool) -> HALResult<()> {
+        hal_call!(HAL_SetCounterSemiPeriodMode(self.handle, high_semi_period as i32))
     }
+}
 
-    pub fn get_stopped(&self) -> HALResult<bool> {
-        Ok(hal_call!(HAL_GetCounterStopped(self.handle))? != 0)
+impl<'a> Counter<'a, PulseLength> {
+    /// Counts pulses on `source`, up if they are shorter than `threshold` seconds and down otherwise.
+    pub fn pulse_length(source: DigitalSource<'a>, threshold: f64) -> HALResult<Self> {
+        let mut counter = Self::initialize()?;
+        counter.set_up_source(source, true, true)?;
+        counter.set_threshold(threshold)?;
+        counter.reset()?;
+        Ok(counter)
     }
 
-    pub fn get_direction(&self) -> HALResult<bool> {
-        Ok(hal_call!(HAL_GetCounterDirection(self.handle))? != 0)
+    pub fn set_threshold(&mut self, threshold: f64) -> HALResult<()> {
+        hal_call!(HAL_SetCounterPulseLengthMode(self.handle, threshold))
     }
 
     pub fn set_reverse_direction(&mut self, reverse_direction: bool) -> HALResult<()> {
         hal_call!(HAL_SetCounterReverseDirection(self.handle, reverse_direction as i32))
     }
-
-    pub unsafe fn raw_handle(&self) -> HAL_CounterHandle {
-        self.handle
-    }
-
 }
 
-impl Drop for Counter {
+impl<'a, M: Mode> Drop for Counter<'a, M> {
     fn drop(&mut self) {
         unsafe { HAL_FreeCounter(self.handle); }
     }
-}
\ No newline at end of file
+}
+
+impl<'a, M: Mode> Handle<HAL_CounterHandle> for Counter<'a, M> {
+    unsafe fn raw_handle(&self) -> HAL_CounterHandle {
+        self.handle
+    }
+
+    unsafe fn from_raw_handle(handle: HAL_CounterHandle) -> Self {
+        Self { handle, index: -1, up_source: None, down_source: None, _mode: PhantomData }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap}, panic::AssertUnwindSafe, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard, PoisonError, Weak}, thread::JoinHandle, time::Duration};
+
+use wpihal_sys::HAL_HANDLE_ERROR;
+
+use crate::{error::{send_error, thread_spawn_error, HALError, HALResult}, get_fpga_time, notifier::Notifier, time::FpgaInstant};
+
+type Action = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Queue {
+    /// (deadline, id) pairs; entries whose id is no longer in `actions` were cancelled.
+    deadlines: BinaryHeap<Reverse<(FpgaInstant, u64)>>,
+    actions: HashMap<u64, Action>,
+    next_id: u64,
+}
+
+struct Shared {
+    notifier: Notifier,
+    queue: Mutex<Queue>,
+    /// Cleared when the worker thread exits.
+    running: AtomicBool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Drops cancelled entries off the top of the queue, then arms the alarm for the earliest
+    /// remaining deadline, or cancels it if there is none.
+    /// Called with the queue locked, so the alarm always matches the queue.
+    fn rearm(&self, queue: &mut Queue) -> HALResult<()> {
+        while let Some(Reverse((_, id))) = queue.deadlines.peek() {
+            if queue.actions.contains_key(id) {
+                break;
+            }
+            queue.deadlines.pop();
+        }
+        match queue.deadlines.peek() {
+            Some(Reverse((deadline, _))) => self.notifier.update_alarm(*deadline),
+            None => self.notifier.cancel_alarm(),
+        }
+    }
+}
+
+/// Runs many one-shot closures at given FPGA times off a single HAL notifier.
+///
+/// Deadlines are kept in a priority queue and only the earliest one is programmed into the
+/// notifier. Closures run in deadline order on a background thread, so they should be short.
+/// A panicking closure is reported to the DS and doesn't stop the others.
+/// Dropping the scheduler stops the thread; closures that haven't run yet are dropped.
+pub struct DeadlineScheduler {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeadlineScheduler {
+    pub fn new() -> HALResult<Self> {
+        let mut notifier = Notifier::initialize()?;
+        notifier.set_name(c"DeadlineScheduler")?;
+        let shared = Arc::new(Shared { notifier, queue: Mutex::new(Queue::default()), running: AtomicBool::new(true) });
+
+        let thread = {
+            let shared = shared.clone();
+            std::thread::Builder::new().name("DeadlineScheduler".to_string()).spawn(move || {
+                let mut due = Vec::new();
+                loop {
+                    let now = match shared.notifier.wait_for_alarm() {
+                        Ok(Some(now)) => now,
+                        // stopped by Drop
+                        Ok(None) => break,
+                        Err(e) => {
+                            send_error(e.0, c"DeadlineScheduler stopped: waiting for the notifier failed").ok();
+                            break;
+                        }
+                    };
+                    {
+                        let mut queue = shared.lock();
+                        while let Some(&Reverse((deadline, id))) = queue.deadlines.peek() {
+                            if deadline > now {
+                                break;
+                            }
+                            queue.deadlines.pop();
+                            if let Some(action) = queue.actions.remove(&id) {
+                                due.push(action);
+                            }
+                        }
+                        if let Err(e) = shared.rearm(&mut queue) {
+                            send_error(e.0, c"DeadlineScheduler stopped: setting the notifier alarm failed").ok();
+                            break;
+                        }
+                    }
+                    // run outside the lock so actions can schedule or cancel other deadlines
+                    for action in due.drain(..) {
+                        if std::panic::catch_unwind(AssertUnwindSafe(action)).is_err() {
+                            send_error(-1, c"a DeadlineScheduler action panicked").ok();
+                        }
+                    }
+                }
+                shared.running.store(false, Ordering::Release);
+            }).map_err(|e| thread_spawn_error("DeadlineScheduler", e))?
+        };
+
+        Ok(Self { shared, thread: Some(thread) })
+    }
+
+    /// Runs `action` at the absolute FPGA time `deadline`, or as soon as possible if it has passed.
+    ///
+    /// Fails with `HAL_HANDLE_ERROR` if the worker thread has stopped after a HAL error.
+    pub fn schedule_at(&self, deadline: FpgaInstant, action: impl FnOnce() + Send + 'static) -> HALResult<DeadlineToken> {
+        if !self.is_running() {
+            return Err(HALError(HAL_HANDLE_ERROR));
+        }
+        let mut queue = self.shared.lock();
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.deadlines.push(Reverse((deadline, id)));
+        queue.actions.insert(id, Box::new(action));
+        self.shared.rearm(&mut queue)?;
+        Ok(DeadlineToken { id, shared: Arc::downgrade(&self.shared) })
+    }
+
+    /// Runs `action` once `delay` has passed.
+    pub fn schedule_in(&self, delay: Duration, action: impl FnOnce() + Send + 'static) -> HALResult<DeadlineToken> {
+        self.schedule_at(get_fpga_time()? + delay, action)
+    }
+
+    /// Whether the worker thread is still running actions.
+    pub fn is_running(&self) -> bool {
+        self.shared.running.load(Ordering::Acquire)
+    }
+
+    /// Number of actions that haven't run or been cancelled yet.
+    pub fn pending(&self) -> usize {
+        self.shared.lock().actions.len()
+    }
+
+    /// Cancels every pending action.
+    pub fn clear(&self) -> HALResult<()> {
+        let mut queue = self.shared.lock();
+        queue.actions.clear();
+        queue.deadlines.clear();
+        self.shared.notifier.cancel_alarm()
+    }
+}
+
+impl Drop for DeadlineScheduler {
+    fn drop(&mut self) {
+        self.shared.notifier.stop().ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Refers to an action scheduled on a [`DeadlineScheduler`].
+///
+/// Dropping the token does not cancel the action.
+#[derive(Debug, Clone)]
+pub struct DeadlineToken {
+    id: u64,
+    shared: Weak<Shared>,
+}
+
+impl DeadlineToken {
+    /// Cancels the action. Returns false if it already ran, was already cancelled,
+    /// or the scheduler is gone.
+    pub fn cancel(&self) -> HALResult<bool> {
+        let Some(shared) = self.shared.upgrade() else {
+            return Ok(false);
+        };
+        let mut queue = shared.lock();
+        if queue.actions.remove(&self.id).is_none() {
+            return Ok(false);
+        }
+        shared.rearm(&mut queue)?;
+        Ok(true)
+    }
+
+    /// Whether the action is still waiting to run.
+    pub fn is_pending(&self) -> bool {
+        self.shared.upgrade().is_some_and(|shared| shared.lock().actions.contains_key(&self.id))
+    }
+}
@@ -1,11 +1,15 @@
-use wpihal_sys::{HAL_AddDMAAnalogAccumulator, HAL_AddDMAAnalogInput, HAL_AddDMAAveragedAnalogInput, HAL_AddDMACounter, HAL_AddDMACounterPeriod, HAL_AddDMADigitalSource, HAL_AddDMADutyCycle, HAL_AddDMAEncoder, HAL_ClearDMAExternalTriggers, HAL_ClearDMASensors, HAL_DMAHandle, HAL_DMASample, HAL_FreeDMA, HAL_GetDMASampleAnalogAccumulator, HAL_GetDMASampleAnalogInputRaw, HAL_GetDMASampleAveragedAnalogInputRaw, HAL_GetDMASampleCounter, HAL_GetDMASampleCounterPeriod, HAL_GetDMASampleDigitalSource, HAL_GetDMASampleDutyCycleOutputRaw, HAL_GetDMASampleEncoderPeriodRaw, HAL_GetDMASampleEncoderRaw, HAL_GetDMASampleTime, HAL_InitializeDMA, HAL_ReadDMA, HAL_SetDMAExternalTrigger, HAL_SetDMAPause, HAL_SetDMATimedTrigger, HAL_SetDMATimedTriggerCycles, HAL_StartDMA, HAL_StopDMA};
+use std::marker::PhantomData;
 
-use crate::{analog_accumulator::AnalogAccumulator, analog_input::AnalogInput, counter::Counter, dio::{DigitalSource, DIO}, duty_cycle::DutyCycle, encoder::Encoder, error::{HALError, HALResult}, hal_call, Handle};
+use wpihal_sys::{HAL_AddDMAAnalogAccumulator, HAL_AddDMAAnalogInput, HAL_AddDMAAveragedAnalogInput, HAL_AddDMACounter, HAL_AddDMACounterPeriod, HAL_AddDMADigitalSource, HAL_AddDMADutyCycle, HAL_AddDMAEncoder, HAL_AddDMAEncoderPeriod, HAL_ClearDMAExternalTriggers, HAL_ClearDMASensors, HAL_DMAHandle, HAL_DMASample, HAL_FreeDMA, HAL_GetDMASampleAnalogAccumulator, HAL_GetDMASampleAnalogInputRaw, HAL_GetDMASampleAveragedAnalogInputRaw, HAL_GetDMASampleCounter, HAL_GetDMASampleCounterPeriod, HAL_GetDMASampleDigitalSource, HAL_GetDMASampleDutyCycleOutputRaw, HAL_GetDMASampleEncoderPeriodRaw, HAL_GetDMASampleEncoderRaw, HAL_GetDMASampleTime, HAL_InitializeDMA, HAL_ReadDMA, HAL_SetDMAExternalTrigger, HAL_SetDMAPause, HAL_SetDMATimedTrigger, HAL_SetDMATimedTriggerCycles, HAL_StartDMA, HAL_StopDMA, HAL_HANDLE_ERROR};
+
+use crate::{analog_accumulator::AnalogAccumulator, analog_input::AnalogInput, counter::{Counter, Mode}, dio::{DigitalSource, DIO}, duty_cycle::DutyCycle, encoder::Encoder, error::{HALError, HALResult}, hal_call, time::FpgaInstant, Handle};
 
 #[derive(Debug, Clone, Copy)]
 pub enum DMAError {
     DMATimeout,
     DMAError,
+    /// The channels added to a [`DMABuilder`] don't match the [`DMARecord`] being read.
+    SchemaMismatch,
     HALError(HALError)
 }
 
@@ -18,6 +22,9 @@ impl core::fmt::Display for DMAError {
             DMAError::DMAError => {
                 write!(f, "DMAError::DMAError")
             }
+            DMAError::SchemaMismatch => {
+                write!(f, "DMAError::SchemaMismatch")
+            }
             DMAError::HALError(halerror) => {
                 write!(f, "DMAError::HALError {} ", halerror)
             }
@@ -38,8 +45,8 @@ impl From<HALError> for DMAError {
 pub struct DMASample(pub HAL_DMASample);
 
 impl DMASample {
-    pub fn get_sample_time(&self) -> HALResult<u64> {
-        hal_call!(HAL_GetDMASampleTime(&self.0))
+    pub fn get_sample_time(&self) -> HALResult<FpgaInstant> {
+        Ok(FpgaInstant::from_micros(hal_call!(HAL_GetDMASampleTime(&self.0))?))
     }
 
     pub fn get_encoder_raw(&self, encoder: &Encoder) -> HALResult<i32> {
@@ -50,11 +57,11 @@ impl DMASample {
         hal_call!(HAL_GetDMASampleEncoderPeriodRaw(&self.0, encoder.raw_handle()))
     }
 
-    pub fn get_counter(&self, counter: &Counter) -> HALResult<i32> {
+    pub fn get_counter<M: Mode>(&self, counter: &Counter<M>) -> HALResult<i32> {
         hal_call!(HAL_GetDMASampleCounter(&self.0, counter.raw_handle()))
     }
 
-    pub fn get_counter_period(&self, counter: &Counter) -> HALResult<i32> {
+    pub fn get_counter_period<M: Mode>(&self, counter: &Counter<M>) -> HALResult<i32> {
         hal_call!(HAL_GetDMASampleCounterPeriod(&self.0, counter.raw_handle()))
     }
 
@@ -112,14 +119,14 @@ impl DMA {
     }
 
     pub fn add_encoder_period(&mut self, encoder: &Encoder) -> HALResult<()> {
-        hal_call!(HAL_AddDMAEncoder(self.0, encoder.raw_handle()))
+        hal_call!(HAL_AddDMAEncoderPeriod(self.0, encoder.raw_handle()))
     }
 
-    pub fn add_counter(&mut self, counter: &Counter) -> HALResult<()> {
+    pub fn add_counter<M: Mode>(&mut self, counter: &Counter<M>) -> HALResult<()> {
         hal_call!(HAL_AddDMACounter(self.0, counter.raw_handle()))
     }
 
-    pub fn add_counter_period(&mut self, counter: &Counter) -> HALResult<()> {
+    pub fn add_counter_period<M: Mode>(&mut self, counter: &Counter<M>) -> HALResult<()> {
         hal_call!(HAL_AddDMACounterPeriod(self.0, counter.raw_handle()))
     }
 
@@ -188,4 +195,255 @@ impl Drop for DMA {
     fn drop(&mut self) {
         unsafe { HAL_FreeDMA(self.0); }
     }
-}
\ No newline at end of file
+}
+
+impl Handle<HAL_DMAHandle> for DMA {
+    unsafe fn raw_handle(&self) -> HAL_DMAHandle {
+        self.0
+    }
+
+    unsafe fn from_raw_handle(handle: HAL_DMAHandle) -> Self {
+        Self(handle)
+    }
+}
+
+/// Kind of value a DMA channel captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DMAChannelKind {
+    Encoder,
+    EncoderPeriod,
+    Counter,
+    CounterPeriod,
+    DigitalSource,
+    AnalogInput,
+    AveragedAnalogInput,
+    AnalogAccumulator,
+    DutyCycle,
+}
+
+/// A sensor added to a [`DMABuilder`], used to pull its value back out of a [`DMASample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DMAChannel {
+    kind: DMAChannelKind,
+    handle: i32,
+}
+
+impl DMAChannel {
+    pub fn kind(&self) -> DMAChannelKind {
+        self.kind
+    }
+
+    /// Reads the raw integer value of any channel other than a digital source or accumulator.
+    pub fn read_raw(&self, sample: &DMASample) -> HALResult<i32> {
+        match self.kind {
+            DMAChannelKind::Encoder => hal_call!(HAL_GetDMASampleEncoderRaw(&sample.0, self.handle)),
+            DMAChannelKind::EncoderPeriod => hal_call!(HAL_GetDMASampleEncoderPeriodRaw(&sample.0, self.handle)),
+            DMAChannelKind::Counter => hal_call!(HAL_GetDMASampleCounter(&sample.0, self.handle)),
+            DMAChannelKind::CounterPeriod => hal_call!(HAL_GetDMASampleCounterPeriod(&sample.0, self.handle)),
+            DMAChannelKind::AnalogInput => hal_call!(HAL_GetDMASampleAnalogInputRaw(&sample.0, self.handle)),
+            DMAChannelKind::AveragedAnalogInput => hal_call!(HAL_GetDMASampleAveragedAnalogInputRaw(&sample.0, self.handle)),
+            DMAChannelKind::DutyCycle => hal_call!(HAL_GetDMASampleDutyCycleOutputRaw(&sample.0, self.handle)),
+            DMAChannelKind::DigitalSource | DMAChannelKind::AnalogAccumulator => Err(HALError(HAL_HANDLE_ERROR)),
+        }
+    }
+
+    pub fn read_digital(&self, sample: &DMASample) -> HALResult<bool> {
+        match self.kind {
+            DMAChannelKind::DigitalSource => Ok(hal_call!(HAL_GetDMASampleDigitalSource(&sample.0, self.handle))? != 0),
+            _ => Err(HALError(HAL_HANDLE_ERROR)),
+        }
+    }
+
+    /// Reads an accumulator channel as `(count, value)`.
+    pub fn read_accumulator(&self, sample: &DMASample) -> HALResult<(i64, i64)> {
+        match self.kind {
+            DMAChannelKind::AnalogAccumulator => {
+                let mut count = 0i64;
+                let mut value = 0i64;
+                hal_call!(HAL_GetDMASampleAnalogAccumulator(&sample.0, self.handle, &mut count, &mut value))?;
+                Ok((count, value))
+            }
+            _ => Err(HALError(HAL_HANDLE_ERROR)),
+        }
+    }
+}
+
+/// A struct that a [`DMASample`] can be decoded into, usually through `#[derive(DMASample)]`.
+///
+/// ```ignore
+/// #[derive(wpihal::DMASample)]
+/// struct Sample {
+///     #[dma(timestamp)]
+///     time: FpgaInstant,
+///     #[dma(encoder)]
+///     drive: i32,
+///     #[dma(digital)]
+///     beam_break: bool,
+/// }
+/// ```
+///
+/// Field attributes are `encoder`, `encoder_period`, `counter`, `counter_period`, `digital`,
+/// `analog`, `averaged_analog`, `accumulator`, `duty_cycle` and `timestamp`. Every field but the timestamp maps
+/// to one channel, in the order the channels were added to the [`DMABuilder`].
+pub trait DMARecord: Sized {
+    /// The channel kinds this record expects, in order.
+    const CHANNELS: &'static [DMAChannelKind];
+
+    fn from_sample(sample: &DMASample, channels: &[DMAChannel]) -> HALResult<Self>;
+}
+
+/// Configures a [`DMA`] while recording which sensors it captures, so samples can be decoded
+/// into a [`DMARecord`] by [`DMABuilder::build`].
+///
+/// The sensors are borrowed for as long as the resulting [`DMAReader`] exists.
+pub struct DMABuilder<'a> {
+    dma: DMA,
+    channels: Vec<DMAChannel>,
+    _sensors: PhantomData<&'a ()>,
+}
+
+impl<'a> DMABuilder<'a> {
+    pub fn new() -> HALResult<Self> {
+        Ok(Self { dma: DMA::initialize()?, channels: Vec::new(), _sensors: PhantomData })
+    }
+
+    pub fn set_timed_trigger(&mut self, period_seconds: f64) -> HALResult<()> {
+        self.dma.set_timed_trigger(period_seconds)
+    }
+
+    pub fn set_timed_trigger_cycles(&mut self, fpga_cycles: u32) -> HALResult<()> {
+        self.dma.set_timed_trigger_cycles(fpga_cycles)
+    }
+
+    pub fn set_external_trigger(&mut self, digital_source: DigitalSource<'a>, rising: bool, falling: bool) -> HALResult<i32> {
+        self.dma.set_external_trigger(&digital_source, rising, falling)
+    }
+
+    pub fn add_encoder(&mut self, encoder: &'a Encoder) -> HALResult<()> {
+        self.dma.add_encoder(encoder)?;
+        self.push(DMAChannelKind::Encoder, unsafe { encoder.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_encoder_period(&mut self, encoder: &'a Encoder) -> HALResult<()> {
+        self.dma.add_encoder_period(encoder)?;
+        self.push(DMAChannelKind::EncoderPeriod, unsafe { encoder.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_counter<M: Mode>(&mut self, counter: &'a Counter<M>) -> HALResult<()> {
+        self.dma.add_counter(counter)?;
+        self.push(DMAChannelKind::Counter, unsafe { counter.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_counter_period<M: Mode>(&mut self, counter: &'a Counter<M>) -> HALResult<()> {
+        self.dma.add_counter_period(counter)?;
+        self.push(DMAChannelKind::CounterPeriod, unsafe { counter.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_digital_source(&mut self, digital_source: &'a DIO) -> HALResult<()> {
+        self.dma.add_digital_source(digital_source)?;
+        self.push(DMAChannelKind::DigitalSource, unsafe { digital_source.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_analog_input(&mut self, analog_input: &'a AnalogInput) -> HALResult<()> {
+        self.dma.add_analog_input(analog_input)?;
+        self.push(DMAChannelKind::AnalogInput, unsafe { analog_input.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_averaged_analog_input(&mut self, analog_input: &'a AnalogInput) -> HALResult<()> {
+        self.dma.add_averaged_analog_input(analog_input)?;
+        self.push(DMAChannelKind::AveragedAnalogInput, unsafe { analog_input.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_analog_accumulator(&mut self, analog_acc: &'a AnalogAccumulator) -> HALResult<()> {
+        self.dma.add_analog_accumulator(analog_acc)?;
+        self.push(DMAChannelKind::AnalogAccumulator, unsafe { analog_acc.raw_handle() });
+        Ok(())
+    }
+
+    pub fn add_duty_cycle(&mut self, duty_cycle: &'a DutyCycle) -> HALResult<()> {
+        self.dma.add_duty_cycle(duty_cycle)?;
+        self.push(DMAChannelKind::DutyCycle, unsafe { duty_cycle.raw_handle() });
+        Ok(())
+    }
+
+    pub fn channels(&self) -> &[DMAChannel] {
+        &self.channels
+    }
+
+    /// Checks the channels against `S` and starts the DMA.
+    pub fn build<S: DMARecord>(mut self, queue_depth: i32) -> Result<DMAReader<'a, S>, DMAError> {
+        if !self.channels.iter().map(DMAChannel::kind).eq(S::CHANNELS.iter().copied()) {
+            return Err(DMAError::SchemaMismatch);
+        }
+        self.dma.start(queue_depth)?;
+        Ok(DMAReader { dma: self.dma, channels: self.channels, _marker: PhantomData })
+    }
+
+    /// Starts the DMA without a record type. The caller is responsible for keeping the sensors alive.
+    pub(crate) fn start(mut self, queue_depth: i32) -> HALResult<(DMA, Vec<DMAChannel>)> {
+        self.dma.start(queue_depth)?;
+        Ok((self.dma, self.channels))
+    }
+
+    fn push(&mut self, kind: DMAChannelKind, handle: i32) {
+        self.channels.push(DMAChannel { kind, handle });
+    }
+}
+
+/// A running [`DMA`] whose samples are decoded into `S`. Created by [`DMABuilder::build`].
+pub struct DMAReader<'a, S> {
+    dma: DMA,
+    channels: Vec<DMAChannel>,
+    _marker: PhantomData<(&'a (), fn() -> S)>,
+}
+
+impl<'a, S: DMARecord> DMAReader<'a, S> {
+    /// Waits up to `timeout_seconds` for a sample, returning it along with the number still queued.
+    pub fn read(&mut self, timeout_seconds: f64) -> Result<(S, i32), DMAError> {
+        let (sample, remaining) = self.dma.read(timeout_seconds)?;
+        Ok((S::from_sample(&sample, &self.channels)?, remaining))
+    }
+
+    /// Appends every queued sample to `out` without blocking, returning how many were read.
+    pub fn read_batch(&mut self, out: &mut Vec<S>) -> Result<usize, DMAError> {
+        let mut count = 0;
+        loop {
+            match self.dma.read(0.0) {
+                Ok((sample, remaining)) => {
+                    out.push(S::from_sample(&sample, &self.channels)?);
+                    count += 1;
+                    if remaining <= 0 {
+                        return Ok(count);
+                    }
+                }
+                Err(DMAError::DMATimeout) => return Ok(count),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn pause(&mut self) -> HALResult<()> {
+        self.dma.pause()
+    }
+
+    pub fn resume(&mut self) -> HALResult<()> {
+        self.dma.resume()
+    }
+
+    pub fn channels(&self) -> &[DMAChannel] {
+        &self.channels
+    }
+
+    /// Stops the DMA, returning it so it can be reconfigured by hand.
+    pub fn stop(mut self) -> HALResult<DMA> {
+        self.dma.stop()?;
+        Ok(self.dma)
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::VecDeque, marker::PhantomData, mem::ManuallyDrop, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard, PoisonError}, thread::JoinHandle, time::Duration};
+
+use crate::{dma::{DMABuilder, DMAChannel, DMAChannelKind, DMAError, DMASample, DMA}, error::{thread_spawn_error, HALResult}, threads::{RtThreadBuilder, RtThreadStatus, ThreadPriority}, time::FpgaInstant, Handle};
+
+/// Read counters kept by a [`DMAHistory`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DMAHistoryStats {
+    /// Samples stored in the history so far, including ones since pushed out.
+    pub samples: u64,
+    /// Reads that timed out with nothing queued.
+    pub timeouts: u64,
+    /// Reads that failed, losing whatever sample they would have returned.
+    pub dropped: u64,
+    pub last_error: Option<DMAError>,
+    /// The most samples seen waiting in the DMA queue after a read.
+    /// If this gets close to the queue depth, the FPGA is likely overwriting samples.
+    pub max_backlog: i32,
+}
+
+struct Shared {
+    capacity: usize,
+    samples: Mutex<VecDeque<(FpgaInstant, DMASample)>>,
+    stats: Mutex<DMAHistoryStats>,
+    running: AtomicBool,
+}
+
+/// Keeps the last `capacity` DMA samples, read on a background real-time thread,
+/// so sensor values can be looked up at past FPGA times (e.g. for latency-compensated vision).
+///
+/// The sensors added to the builder are borrowed for as long as the history exists.
+/// Dropping it stops the thread, then stops and frees the DMA.
+pub struct DMAHistory<'a> {
+    shared: Arc<Shared>,
+    channels: Vec<DMAChannel>,
+    thread: Option<JoinHandle<()>>,
+    thread_status: RtThreadStatus,
+    _dma: DMA,
+    _sensors: PhantomData<&'a ()>,
+}
+
+impl<'a> DMAHistory<'a> {
+    /// How long each read waits before checking whether the history is being dropped, in seconds.
+    const READ_TIMEOUT: f64 = 0.1;
+
+    /// Starts the DMA configured by `builder` and begins recording samples.
+    ///
+    /// * `queue_depth` - DMA queue depth, passed to [`DMA::start`]
+    /// * `capacity` - number of samples kept
+    /// * `priority` - priority of the reading thread
+    pub fn new(builder: DMABuilder<'a>, queue_depth: i32, capacity: usize, priority: ThreadPriority) -> HALResult<Self> {
+        let (dma, channels) = builder.start(queue_depth)?;
+        let capacity = capacity.max(2);
+        let shared = Arc::new(Shared {
+            capacity,
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+            stats: Mutex::new(DMAHistoryStats::default()),
+            running: AtomicBool::new(true),
+        });
+
+        // the thread only gets the raw handle; Drop joins it before the DMA is freed
+        let handle = unsafe { dma.raw_handle() };
+        let timeout = Self::READ_TIMEOUT;
+        let (thread, thread_status) = {
+            let shared = shared.clone();
+            RtThreadBuilder::new("DMAHistory").priority(priority).spawn(move || {
+                let mut dma = ManuallyDrop::new(unsafe { DMA::from_raw_handle(handle) });
+                while shared.running.load(Ordering::Acquire) {
+                    if !shared.record(dma.read(timeout)) {
+                        // don't spin at RT priority if the DMA keeps failing
+                        std::thread::sleep(Duration::from_secs_f64(timeout));
+                    }
+                }
+            }).map_err(|e| thread_spawn_error("DMAHistory", e))?
+        };
+
+        Ok(Self { shared, channels, thread: Some(thread), thread_status, _dma: dma, _sensors: PhantomData })
+    }
+
+    /// The channels recorded, in the order they were added to the builder.
+    pub fn channels(&self) -> &[DMAChannel] {
+        &self.channels
+    }
+
+    /// Whether the reading thread got the requested priority.
+    pub fn thread_status(&self) -> RtThreadStatus {
+        self.thread_status
+    }
+
+    pub fn stats(&self) -> DMAHistoryStats {
+        *self.shared.stats.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Time span currently covered by the history.
+    pub fn time_range(&self) -> Option<(FpgaInstant, FpgaInstant)> {
+        let samples = self.shared.lock();
+        Some((samples.front()?.0, samples.back()?.0))
+    }
+
+    /// Value of `channel` at `time`, linearly interpolated between the samples on either side.
+    ///
+    /// Digital channels read as 0 or 1 and take the value of the earlier sample; accumulators
+    /// give their value. Returns `None` if `time` is outside the recorded range.
+    pub fn value_at(&self, channel: &DMAChannel, time: FpgaInstant) -> HALResult<Option<f64>> {
+        let samples = self.shared.lock();
+        let after = samples.partition_point(|(t, _)| *t < time);
+        let Some((t1, s1)) = samples.get(after) else {
+            return Ok(None);
+        };
+        if *t1 == time {
+            return Ok(Some(Self::channel_value(channel, s1)?));
+        }
+        let Some((t0, s0)) = after.checked_sub(1).and_then(|i| samples.get(i)) else {
+            return Ok(None);
+        };
+
+        let v0 = Self::channel_value(channel, s0)?;
+        if channel.kind() == DMAChannelKind::DigitalSource {
+            return Ok(Some(v0));
+        }
+        let v1 = Self::channel_value(channel, s1)?;
+        let frac = (time - *t0).as_secs_f64() / (*t1 - *t0).as_secs_f64();
+        Ok(Some(v0 + (v1 - v0) * frac))
+    }
+
+    /// The most recent value of `channel`, along with its sample time.
+    pub fn latest(&self, channel: &DMAChannel) -> HALResult<Option<(FpgaInstant, f64)>> {
+        let samples = self.shared.lock();
+        match samples.back() {
+            Some((time, sample)) => Ok(Some((*time, Self::channel_value(channel, sample)?))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.shared.lock().clear();
+    }
+
+    fn channel_value(channel: &DMAChannel, sample: &DMASample) -> HALResult<f64> {
+        Ok(match channel.kind() {
+            DMAChannelKind::DigitalSource => channel.read_digital(sample)? as u8 as f64,
+            DMAChannelKind::AnalogAccumulator => channel.read_accumulator(sample)?.1 as f64,
+            _ => channel.read_raw(sample)? as f64,
+        })
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, VecDeque<(FpgaInstant, DMASample)>> {
+        self.samples.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stores the result of a read, returning false if it failed.
+    fn record(&self, read: Result<(DMASample, i32), DMAError>) -> bool {
+        let sample = read.and_then(|(sample, remaining)| Ok((sample.get_sample_time()?, sample, remaining)));
+        let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+        match sample {
+            Ok((time, sample, remaining)) => {
+                let mut samples = self.lock();
+                if samples.len() >= self.capacity {
+                    samples.pop_front();
+                }
+                samples.push_back((time, sample));
+                stats.samples += 1;
+                stats.max_backlog = stats.max_backlog.max(remaining);
+                true
+            }
+            Err(DMAError::DMATimeout) => {
+                stats.timeouts += 1;
+                true
+            }
+            Err(e) => {
+                stats.dropped += 1;
+                stats.last_error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+impl<'a> Drop for DMAHistory<'a> {
+    fn drop(&mut self) {
+        self.shared.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
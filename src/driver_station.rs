@@ -1,4 +1,6 @@
-use wpihal_sys::{HAL_AllianceStationID, HAL_ControlWord, HAL_GetAllianceStation, HAL_GetControlWord, HAL_GetJoystickAxes, HAL_GetJoystickAxisType, HAL_GetJoystickDescriptor, HAL_GetJoystickIsXbox, HAL_GetJoystickName, HAL_GetJoystickPOVs, HAL_GetJoystickType, HAL_GetMatchInfo, HAL_GetMatchTime, HAL_GetOutputsEnabled, HAL_JoystickAxes, HAL_JoystickButtons, HAL_JoystickDescriptor, HAL_JoystickPOVs, HAL_MatchInfo, HAL_MatchType, HAL_ObserveUserProgramAutonomous, HAL_ObserveUserProgramDisabled, HAL_ObserveUserProgramStarting, HAL_ObserveUserProgramTeleop, HAL_ObserveUserProgramTest, HAL_RefreshDSData, HAL_SetJoystickOutputs, WPI_String};
+use std::ffi::CStr;
+
+use wpihal_sys::{HAL_AllianceStationID, HAL_ControlWord, HAL_GetAllianceStation, HAL_GetControlWord, HAL_GetJoystickAxes, HAL_GetJoystickAxisType, HAL_GetJoystickButtons, HAL_GetJoystickDescriptor, HAL_GetJoystickIsXbox, HAL_GetJoystickName, HAL_GetJoystickPOVs, HAL_GetJoystickType, HAL_GetMatchInfo, HAL_GetMatchTime, HAL_GetOutputsEnabled, HAL_JoystickAxes, HAL_JoystickButtons, HAL_JoystickDescriptor, HAL_JoystickPOVs, HAL_MatchInfo, HAL_MatchType, HAL_ObserveUserProgramAutonomous, HAL_ObserveUserProgramDisabled, HAL_ObserveUserProgramStarting, HAL_ObserveUserProgramTeleop, HAL_ObserveUserProgramTest, HAL_RefreshDSData, HAL_SetJoystickOutputs, WPI_String};
 use wpiutil::wpistring::WPIString;
 
 use crate::{error::{HALError, HALResult}, hal_call};
@@ -70,11 +72,11 @@ pub fn get_joystick_povs(joystick_num: i32) -> HALResult<JoystickPOVs> {
     }
 }
 
-pub fn get_joystick_buttons(joystick_num: i32) -> HALResult<JoystickPOVs> {
+pub fn get_joystick_buttons(joystick_num: i32) -> HALResult<JoystickButtons> {
     unsafe {
-        let mut povs: HAL_JoystickPOVs = core::mem::zeroed();
-        match HAL_GetJoystickPOVs(joystick_num, &mut povs) {
-            0 => Ok(povs),
+        let mut buttons: HAL_JoystickButtons = core::mem::zeroed();
+        match HAL_GetJoystickButtons(joystick_num, &mut buttons) {
+            0 => Ok(buttons),
             err => Err(HALError(err))
         }
     }
@@ -90,6 +92,11 @@ pub fn get_joystick_descriptor(joystick_num: i32) -> HALResult<JoystickDescripto
     }
 }
 
+/// Fetches the joystick descriptor and converts it into a [`ControllerDescriptor`].
+pub fn get_controller_descriptor(joystick_num: i32) -> HALResult<ControllerDescriptor> {
+    Ok(ControllerDescriptor::from(&get_joystick_descriptor(joystick_num)?))
+}
+
 pub fn get_joystick_is_xbox(joystick_num: i32) -> bool {
     unsafe { HAL_GetJoystickIsXbox(joystick_num) != 0 }
 }
@@ -99,6 +106,10 @@ pub fn get_joystick_type(joystick_num: i32) -> i32 {
     unsafe { HAL_GetJoystickType(joystick_num) }
 }
 
+pub fn get_joystick_hid_type(joystick_num: i32) -> HIDType {
+    HIDType::from(get_joystick_type(joystick_num))
+}
+
 pub fn get_joystick_name(joystick_num: i32) -> WPIString {
     let mut name = WPI_String::default(); 
     unsafe {
@@ -111,6 +122,10 @@ pub fn get_joystick_axis_type(joystick_num: i32, axis: i32) -> i32 {
     unsafe { HAL_GetJoystickAxisType(joystick_num, axis) }
 }
 
+pub fn get_joystick_axis_kind(joystick_num: i32, axis: i32) -> AxisType {
+    AxisType::from(get_joystick_axis_type(joystick_num, axis))
+}
+
 pub fn set_joystick_outputs(joystick_num: i32, outputs: u64, left_rumble: u16, right_rumble: u16) -> HALResult<()> {
     unsafe {
         match HAL_SetJoystickOutputs(joystick_num, outputs as i64, left_rumble as i32, right_rumble as i32) {
@@ -201,4 +216,135 @@ pub type JoystickAxes = HAL_JoystickAxes;
 pub type JoystickPOVs = HAL_JoystickPOVs;
 pub type JoystickButtons = HAL_JoystickButtons;
 pub type JoystickDescriptor = HAL_JoystickDescriptor;
-pub type MatchInfo = HAL_MatchInfo;
\ No newline at end of file
+pub type MatchInfo = HAL_MatchInfo;
+
+/// The kind of device the driver station reports for a joystick slot.
+///
+/// XInput devices are what the DS calls "Xbox" controllers;
+/// everything else comes through as a generic HID device.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HIDType {
+    Unknown = -1,
+    XInputUnknown = 0,
+    XInputGamepad = 1,
+    XInputWheel = 2,
+    XInputArcadeStick = 3,
+    XInputFlightStick = 4,
+    XInputDancePad = 5,
+    XInputGuitar = 6,
+    XInputGuitar2 = 7,
+    XInputDrumKit = 8,
+    XInputGuitar3 = 11,
+    XInputArcadePad = 19,
+    HIDJoystick = 20,
+    HIDGamepad = 21,
+    HIDDriving = 22,
+    HIDFlight = 23,
+    HID1stPerson = 24,
+}
+
+impl HIDType {
+    pub fn is_xinput(&self) -> bool {
+        matches!(*self as i32, 0..=19)
+    }
+}
+
+impl From<i32> for HIDType {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::XInputUnknown,
+            1 => Self::XInputGamepad,
+            2 => Self::XInputWheel,
+            3 => Self::XInputArcadeStick,
+            4 => Self::XInputFlightStick,
+            5 => Self::XInputDancePad,
+            6 => Self::XInputGuitar,
+            7 => Self::XInputGuitar2,
+            8 => Self::XInputDrumKit,
+            11 => Self::XInputGuitar3,
+            19 => Self::XInputArcadePad,
+            20 => Self::HIDJoystick,
+            21 => Self::HIDGamepad,
+            22 => Self::HIDDriving,
+            23 => Self::HIDFlight,
+            24 => Self::HID1stPerson,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// The HID usage the driver station reports for a joystick axis.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisType {
+    X = 0,
+    Y = 1,
+    Z = 2,
+    Rx = 3,
+    Ry = 4,
+    Rz = 5,
+    Slider = 6,
+    Dial = 7,
+    Wheel = 8,
+    Unknown = -1,
+}
+
+impl From<i32> for AxisType {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::X,
+            1 => Self::Y,
+            2 => Self::Z,
+            3 => Self::Rx,
+            4 => Self::Ry,
+            5 => Self::Rz,
+            6 => Self::Slider,
+            7 => Self::Dial,
+            8 => Self::Wheel,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Typed version of [`JoystickDescriptor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerDescriptor {
+    pub is_xbox: bool,
+    pub hid_type: HIDType,
+    pub name: String,
+    /// One entry per axis the controller reports.
+    pub axis_types: Vec<AxisType>,
+    pub button_count: u8,
+    pub pov_count: u8,
+}
+
+impl ControllerDescriptor {
+    pub fn axis_count(&self) -> usize {
+        self.axis_types.len()
+    }
+
+    /// Whether anything is plugged into this slot at all.
+    pub fn is_connected(&self) -> bool {
+        self.axis_count() > 0 || self.button_count > 0 || self.pov_count > 0
+    }
+}
+
+impl From<&JoystickDescriptor> for ControllerDescriptor {
+    fn from(desc: &JoystickDescriptor) -> Self {
+        let axis_count = (desc.axisCount as usize).min(desc.axisTypes.len());
+        let name = if desc.name.contains(&0) {
+            unsafe { CStr::from_ptr(desc.name.as_ptr()) }.to_string_lossy().to_string()
+        } else {
+            String::new()
+        };
+        Self {
+            is_xbox: desc.isXbox != 0,
+            hid_type: HIDType::from(desc.type_ as i32),
+            name,
+            axis_types: desc.axisTypes[..axis_count].iter().map(|t| AxisType::from(*t as i32)).collect(),
+            button_count: desc.buttonCount,
+            pov_count: desc.povCount,
+        }
+    }
+}
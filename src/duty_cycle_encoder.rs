@@ -0,0 +1,210 @@
+use crate::{analog_trigger::{AnalogTrigger, AnalogTriggerType}, dio::DIO, duty_cycle::DutyCycle, error::HALResult};
+
+/// Absolute encoder that reports its angle as a PWM duty cycle, such as the REV Through Bore
+/// Encoder or CTRE Mag Encoder.
+///
+/// [`DutyCycleEncoder::get_absolute_position`] gives the angle within one rotation.
+/// To track whole turns as well, call [`DutyCycleEncoder::update`] often enough that the shaft
+/// turns less than half a rotation between calls.
+#[derive(Debug)]
+pub struct DutyCycleEncoder<'a> {
+    duty_cycle: DutyCycle<'a>,
+    mapping: PositionMapping,
+    distance_per_rotation: f64,
+    connected_frequency_threshold: i32,
+    turns: TurnCounter,
+}
+
+/// Maps a duty cycle onto a position within one rotation.
+#[derive(Debug, Clone, Copy)]
+struct PositionMapping {
+    min_duty_cycle: f64,
+    max_duty_cycle: f64,
+    zero_offset: f64,
+    inverted: bool,
+}
+
+impl Default for PositionMapping {
+    fn default() -> Self {
+        Self { min_duty_cycle: 0.0, max_duty_cycle: 1.0, zero_offset: 0.0, inverted: false }
+    }
+}
+
+impl PositionMapping {
+    fn position(&self, output: f64) -> f64 {
+        let output = output.clamp(self.min_duty_cycle, self.max_duty_cycle);
+        let span = self.max_duty_cycle - self.min_duty_cycle;
+        let position = if span > 0.0 { (output - self.min_duty_cycle) / span } else { 0.0 };
+        let position = (position - self.zero_offset).rem_euclid(1.0);
+        if self.inverted { (1.0 - position).rem_euclid(1.0) } else { position }
+    }
+}
+
+/// Counts whole turns from successive positions within one rotation.
+#[derive(Debug, Clone, Copy, Default)]
+struct TurnCounter {
+    turns: i64,
+    last_position: Option<f64>,
+}
+
+impl TurnCounter {
+    /// Assumes the shaft turned less than half a rotation since the last position.
+    fn update(&mut self, position: f64) {
+        if let Some(last) = self.last_position {
+            if position - last < -0.5 {
+                self.turns += 1;
+            } else if position - last > 0.5 {
+                self.turns -= 1;
+            }
+        }
+        self.last_position = Some(position);
+    }
+
+    fn rotations(&self) -> f64 {
+        self.turns as f64 + self.last_position.unwrap_or(0.0)
+    }
+}
+
+impl<'a> DutyCycleEncoder<'a> {
+    pub fn new(duty_cycle: DutyCycle<'a>) -> Self {
+        Self {
+            duty_cycle,
+            mapping: PositionMapping::default(),
+            distance_per_rotation: 1.0,
+            connected_frequency_threshold: 100,
+            turns: TurnCounter::default(),
+        }
+    }
+
+    pub fn initialize_from_dio(dio: &'a DIO) -> HALResult<Self> {
+        Ok(Self::new(DutyCycle::initialize_from_dio(dio)?))
+    }
+
+    pub fn initialize_from_analog_trigger(trg: &'a AnalogTrigger<'a>, trg_type: AnalogTriggerType) -> HALResult<Self> {
+        Ok(Self::new(DutyCycle::initialize_from_analog_trigger(trg, trg_type)?))
+    }
+
+    /// The underlying duty cycle input.
+    pub fn duty_cycle(&self) -> &DutyCycle<'a> {
+        &self.duty_cycle
+    }
+
+    /// Sets the duty cycles the encoder outputs at the ends of its range, e.g. 1/1025 and 1024/1025
+    /// for the REV Through Bore Encoder. Readings outside the range are clamped to it.
+    pub fn set_duty_cycle_range(&mut self, min: f64, max: f64) {
+        self.mapping.min_duty_cycle = min.clamp(0.0, 1.0);
+        self.mapping.max_duty_cycle = max.clamp(self.mapping.min_duty_cycle, 1.0);
+    }
+
+    /// Sets the absolute position, in rotations from 0 to 1, that reads as zero.
+    pub fn set_zero_offset(&mut self, offset: f64) {
+        self.mapping.zero_offset = offset.rem_euclid(1.0);
+    }
+
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.mapping.inverted = inverted;
+    }
+
+    pub fn set_distance_per_rotation(&mut self, distance_per_rotation: f64) {
+        self.distance_per_rotation = distance_per_rotation;
+    }
+
+    /// Sets the PWM frequency, in Hz, below which the encoder counts as disconnected. Defaults to 100.
+    pub fn set_connected_frequency_threshold(&mut self, threshold: i32) {
+        self.connected_frequency_threshold = threshold.max(0);
+    }
+
+    pub fn is_connected(&self) -> HALResult<bool> {
+        Ok(self.duty_cycle.get_frequency()? > self.connected_frequency_threshold)
+    }
+
+    /// Position within one rotation, from 0 to 1, after the range, offset and inversion are applied.
+    pub fn get_absolute_position(&self) -> HALResult<f64> {
+        Ok(self.mapping.position(self.duty_cycle.get_output()?))
+    }
+
+    /// Samples the encoder, counting a turn whenever the position wraps around,
+    /// and returns the total rotations. Readings are ignored while disconnected.
+    pub fn update(&mut self) -> HALResult<f64> {
+        if self.is_connected()? {
+            let position = self.get_absolute_position()?;
+            self.turns.update(position);
+        }
+        Ok(self.get_rotations())
+    }
+
+    /// Total rotations as of the last [`DutyCycleEncoder::update`].
+    pub fn get_rotations(&self) -> f64 {
+        self.turns.rotations()
+    }
+
+    /// Total distance as of the last [`DutyCycleEncoder::update`].
+    pub fn get_distance(&self) -> f64 {
+        self.get_rotations() * self.distance_per_rotation
+    }
+
+    /// Forgets the turn count, keeping only the absolute position.
+    pub fn reset_turns(&mut self) {
+        self.turns.turns = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn full_range() {
+        let mapping = PositionMapping::default();
+        assert_close(mapping.position(0.25), 0.25);
+        // a full rotation wraps back to zero
+        assert_close(mapping.position(1.0), 0.0);
+    }
+
+    #[test]
+    fn duty_cycle_range() {
+        let mapping = PositionMapping { min_duty_cycle: 1.0 / 1025.0, max_duty_cycle: 1024.0 / 1025.0, ..Default::default() };
+        assert_close(mapping.position(1.0 / 1025.0), 0.0);
+        assert_close(mapping.position(512.5 / 1025.0), 0.5);
+        // clamped below the range
+        assert_close(mapping.position(0.0), 0.0);
+    }
+
+    #[test]
+    fn empty_range() {
+        let mapping = PositionMapping { min_duty_cycle: 0.5, max_duty_cycle: 0.5, ..Default::default() };
+        assert_close(mapping.position(0.7), 0.0);
+    }
+
+    #[test]
+    fn zero_offset_wraps() {
+        let mapping = PositionMapping { zero_offset: 0.75, ..Default::default() };
+        assert_close(mapping.position(0.8), 0.05);
+        assert_close(mapping.position(0.25), 0.5);
+    }
+
+    #[test]
+    fn inverted() {
+        let mapping = PositionMapping { zero_offset: 0.25, inverted: true, ..Default::default() };
+        assert_close(mapping.position(0.25), 0.0);
+        assert_close(mapping.position(0.35), 0.9);
+    }
+
+    #[test]
+    fn counts_turns_across_the_wrap() {
+        let mut turns = TurnCounter::default();
+        assert_close(turns.rotations(), 0.0);
+        for position in [0.6, 0.9, 0.1, 0.4, 0.8, 0.2] {
+            turns.update(position);
+        }
+        assert_close(turns.rotations(), 2.2);
+        for position in [0.9, 0.5, 0.1, 0.7] {
+            turns.update(position);
+        }
+        assert_close(turns.rotations(), 0.7);
+    }
+}
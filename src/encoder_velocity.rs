@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+
+use crate::{dma::DMASample, encoder::Encoder, error::HALResult, get_fpga_time, time::FpgaInstant};
+
+/// How an [`EncoderVelocityEstimator`] turns position samples into a velocity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VelocityAlgorithm {
+    /// Change in position between the oldest and newest sample in the window, over the time between them.
+    MovingAverage,
+    /// Least-squares slope of position against time over the window. Smoother, but lags more.
+    LinearRegression,
+    /// Uses the FPGA's pulse period (1/T) at low speed, where few counts land in the window, and
+    /// the moving average (M/T) once the window holds at least `switch_counts` counts.
+    ///
+    /// The period is only available from [`EncoderVelocityEstimator::update`]; samples fed from DMA
+    /// always use M/T.
+    PeriodSwitching { switch_counts: u32 },
+}
+
+/// Estimates encoder velocity from raw count deltas, which is much less noisy at low speed than
+/// [`Encoder::get_rate`].
+///
+/// Feed it either by calling [`EncoderVelocityEstimator::update`] every loop, or with DMA samples
+/// through [`EncoderVelocityEstimator::add_dma_sample`] for exact timestamps.
+/// Velocities are in the encoder's `distance_per_pulse` units per second, and any velocity
+/// below the stopped threshold reads as zero.
+#[derive(Debug, Clone)]
+pub struct EncoderVelocityEstimator {
+    algorithm: VelocityAlgorithm,
+    window: usize,
+    samples: VecDeque<(FpgaInstant, i32)>,
+    distance_per_count: f64,
+    distance_per_pulse: f64,
+    stopped_threshold: f64,
+    /// Period-based velocity from the last [`EncoderVelocityEstimator::update`].
+    period_velocity: Option<f64>,
+    velocity: f64,
+}
+
+impl EncoderVelocityEstimator {
+    /// Creates an estimator over the last `window` samples (at least 2).
+    ///
+    /// The scale is read from the encoder now, so set its distance per pulse first.
+    pub fn new(encoder: &Encoder, algorithm: VelocityAlgorithm, window: usize) -> HALResult<Self> {
+        let distance_per_pulse = encoder.get_distance_per_pulse()?;
+        let window = window.max(2);
+        Ok(Self {
+            algorithm,
+            window,
+            samples: VecDeque::with_capacity(window),
+            // raw counts are in the encoding's edges, e.g. four per pulse for 4X decoding
+            distance_per_count: distance_per_pulse * encoder.get_decoding_scale_factor()?,
+            distance_per_pulse,
+            stopped_threshold: 0.0,
+            period_velocity: None,
+            velocity: 0.0,
+        })
+    }
+
+    pub fn set_algorithm(&mut self, algorithm: VelocityAlgorithm) {
+        self.algorithm = algorithm;
+    }
+
+    /// Velocities with a smaller magnitude than this read as zero.
+    pub fn set_stopped_threshold(&mut self, threshold: f64) {
+        self.stopped_threshold = threshold.abs();
+    }
+
+    /// Samples the encoder now and returns the new estimate.
+    pub fn update(&mut self, encoder: &Encoder) -> HALResult<f64> {
+        let raw = encoder.get_raw()?;
+        let time = get_fpga_time()?;
+        if let VelocityAlgorithm::PeriodSwitching { .. } = self.algorithm {
+            self.period_velocity = Some(if encoder.get_stopped()? {
+                0.0
+            } else {
+                let rate = self.distance_per_pulse / encoder.get_period()?;
+                if encoder.get_direction()? { rate } else { -rate }
+            });
+        }
+        self.push(time, raw);
+        Ok(self.velocity)
+    }
+
+    /// Adds the encoder's value from a DMA sample, using the sample's timestamp.
+    pub fn add_dma_sample(&mut self, sample: &DMASample, encoder: &Encoder) -> HALResult<f64> {
+        let time = sample.get_sample_time()?;
+        let raw = sample.get_encoder_raw(encoder)?;
+        self.add_sample(time, raw);
+        Ok(self.velocity)
+    }
+
+    /// Adds a raw count taken at `time`. Samples must be added in time order.
+    pub fn add_sample(&mut self, time: FpgaInstant, raw: i32) {
+        self.period_velocity = None;
+        self.push(time, raw);
+    }
+
+    /// The latest estimate.
+    pub fn velocity(&self) -> f64 {
+        self.velocity
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.velocity == 0.0
+    }
+
+    /// Forgets all samples.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.period_velocity = None;
+        self.velocity = 0.0;
+    }
+
+    fn push(&mut self, time: FpgaInstant, raw: i32) {
+        if self.samples.back().is_some_and(|(last, _)| time <= *last) {
+            // duplicate or out of order
+            return;
+        }
+        if self.samples.len() >= self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, raw));
+
+        let velocity = match self.algorithm {
+            VelocityAlgorithm::MovingAverage => self.moving_average(),
+            VelocityAlgorithm::LinearRegression => self.linear_regression(),
+            VelocityAlgorithm::PeriodSwitching { switch_counts } => {
+                let counts = self.window_counts().unsigned_abs();
+                match self.period_velocity {
+                    Some(period_velocity) if counts < switch_counts as u64 => period_velocity,
+                    _ => self.moving_average(),
+                }
+            }
+        };
+        self.velocity = if velocity.is_finite() && velocity.abs() >= self.stopped_threshold { velocity } else { 0.0 };
+    }
+
+    fn window_counts(&self) -> i64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((_, first)), Some((_, last))) => *last as i64 - *first as i64,
+            _ => 0,
+        }
+    }
+
+    fn moving_average(&self) -> f64 {
+        let (Some((t0, _)), Some((t1, _))) = (self.samples.front(), self.samples.back()) else {
+            return 0.0;
+        };
+        let dt = (*t1 - *t0).as_secs_f64();
+        if dt <= 0.0 {
+            return 0.0;
+        }
+        self.window_counts() as f64 * self.distance_per_count / dt
+    }
+
+    fn linear_regression(&self) -> f64 {
+        let Some((t0, x0)) = self.samples.front() else {
+            return 0.0;
+        };
+        let n = self.samples.len() as f64;
+        // relative to the first sample to keep the sums well conditioned
+        let points = || self.samples.iter().map(|(t, x)| ((*t - *t0).as_secs_f64(), (*x as i64 - *x0 as i64) as f64));
+        let (sum_t, sum_x) = points().fold((0.0, 0.0), |(st, sx), (t, x)| (st + t, sx + x));
+        let (mean_t, mean_x) = (sum_t / n, sum_x / n);
+        let (cov, var) = points().fold((0.0, 0.0), |(cov, var), (t, x)| {
+            (cov + (t - mean_t) * (x - mean_x), var + (t - mean_t) * (t - mean_t))
+        });
+        if var <= 0.0 {
+            return 0.0;
+        }
+        cov / var * self.distance_per_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimator(algorithm: VelocityAlgorithm, window: usize) -> EncoderVelocityEstimator {
+        EncoderVelocityEstimator {
+            algorithm,
+            window,
+            samples: VecDeque::new(),
+            distance_per_count: 0.5,
+            distance_per_pulse: 2.0,
+            stopped_threshold: 0.0,
+            period_velocity: None,
+            velocity: 0.0,
+        }
+    }
+
+    fn ms(millis: u64) -> FpgaInstant {
+        FpgaInstant::from_micros(millis * 1000)
+    }
+
+    /// Feeds the period velocity an `update` would have read, followed by the raw count.
+    fn push_with_period(estimator: &mut EncoderVelocityEstimator, time: FpgaInstant, raw: i32, period_velocity: f64) {
+        estimator.period_velocity = Some(period_velocity);
+        estimator.push(time, raw);
+    }
+
+    #[test]
+    fn moving_average_uses_window_ends() {
+        let mut estimator = estimator(VelocityAlgorithm::MovingAverage, 3);
+        estimator.add_sample(ms(0), 0);
+        estimator.add_sample(ms(10), 100);
+        estimator.add_sample(ms(20), 100);
+        // 100 counts * 0.5 over 20 ms
+        assert_eq!(estimator.velocity(), 2500.0);
+        estimator.add_sample(ms(30), 100);
+        // the first sample has dropped out of the window
+        assert_eq!(estimator.velocity(), 0.0);
+        assert!(estimator.is_stopped());
+    }
+
+    #[test]
+    fn linear_regression_fits_slope() {
+        let mut estimator = estimator(VelocityAlgorithm::LinearRegression, 4);
+        for i in 0..4 {
+            estimator.add_sample(ms(i * 10), -(i as i32) * 20);
+        }
+        // -20 counts * 0.5 every 10 ms
+        assert!((estimator.velocity() + 1000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn out_of_order_samples_are_ignored() {
+        let mut estimator = estimator(VelocityAlgorithm::MovingAverage, 3);
+        estimator.add_sample(ms(10), 0);
+        estimator.add_sample(ms(20), 10);
+        estimator.add_sample(ms(20), 1000);
+        estimator.add_sample(ms(5), 1000);
+        assert_eq!(estimator.samples.len(), 2);
+        assert_eq!(estimator.velocity(), 500.0);
+    }
+
+    #[test]
+    fn stopped_threshold() {
+        let mut estimator = estimator(VelocityAlgorithm::MovingAverage, 2);
+        estimator.set_stopped_threshold(-100.0);
+        estimator.add_sample(ms(0), 0);
+        estimator.add_sample(ms(100), 10);
+        assert_eq!(estimator.velocity(), 0.0);
+        estimator.add_sample(ms(200), 50);
+        assert_eq!(estimator.velocity(), 200.0);
+    }
+
+    #[test]
+    fn period_switching_switches_on_counts() {
+        let mut estimator = estimator(VelocityAlgorithm::PeriodSwitching { switch_counts: 10 }, 2);
+        push_with_period(&mut estimator, ms(0), 0, 7.0);
+        push_with_period(&mut estimator, ms(10), 9, 7.0);
+        // fewer than 10 counts in the window: 1/T
+        assert_eq!(estimator.velocity(), 7.0);
+        push_with_period(&mut estimator, ms(20), 19, 7.0);
+        // 10 counts: M/T
+        assert_eq!(estimator.velocity(), 500.0);
+        // the count is by magnitude, so reversing past it is M/T too
+        push_with_period(&mut estimator, ms(30), -1, 7.0);
+        assert_eq!(estimator.velocity(), -1000.0);
+    }
+
+    #[test]
+    fn period_switching_without_period_uses_moving_average() {
+        let mut estimator = estimator(VelocityAlgorithm::PeriodSwitching { switch_counts: 10 }, 2);
+        estimator.add_sample(ms(0), 0);
+        estimator.add_sample(ms(10), 2);
+        assert_eq!(estimator.velocity(), 100.0);
+    }
+
+    #[test]
+    fn reset_forgets_samples() {
+        let mut estimator = estimator(VelocityAlgorithm::MovingAverage, 2);
+        estimator.add_sample(ms(0), 0);
+        estimator.add_sample(ms(10), 10);
+        estimator.reset();
+        assert_eq!(estimator.velocity(), 0.0);
+        estimator.add_sample(ms(20), 1000);
+        assert_eq!(estimator.velocity(), 0.0);
+    }
+}
@@ -1,10 +1,12 @@
 // Parts borrowed from https://github.com/first-rust-competition/first-rust-competition/blob/master/wpilib-sys/src/hal_call.rs
 
 use core::fmt;
-use std::{borrow::Cow, ffi::{CStr, CString}};
+use std::{backtrace::Backtrace, borrow::Cow, ffi::{CStr, CString}};
 
 use wpihal_sys::{HAL_GetErrorMessage, HAL_SendConsoleLine, HAL_SendError};
 
+use crate::main_loop::ROBOT_THREAD_NAME;
+
 /// Sends a warning to the driver station.
 pub fn send_warning(code: i32, details: &CStr) -> HALResult<()> {
     let v = unsafe {
@@ -36,6 +38,27 @@ pub fn send_error(code: i32, details: &CStr) -> HALResult<()> {
     };
     if v != 0 { Err(HALError(v)) } else { Ok(()) }
 }
+
+/// Sends an error or warning to the driver station with the location and call stack fields filled in.
+///
+/// * `is_error` - true for an error, false for a warning
+/// * `location` - where the error happened, typically `file:line:column`
+/// * `call_stack` - shown in the DS when the message is expanded
+/// * `print_msg` - whether to also print the message to the console
+pub fn send_error_with_context(is_error: bool, code: i32, details: &CStr, location: &CStr, call_stack: &CStr, print_msg: bool) -> HALResult<()> {
+    let v = unsafe {
+        HAL_SendError(
+            is_error as i32,
+            code,
+            0,
+            details.as_ptr(),
+            location.as_ptr(),
+            call_stack.as_ptr(),
+            print_msg as i32
+        )
+    };
+    if v != 0 { Err(HALError(v)) } else { Ok(()) }
+}
 // We don't bother with HAL_SetPrintErrorImpl because frankly it's kinda nuts.
 
 pub fn send_console_line(line: &str) -> HALResult<()> {
@@ -44,6 +67,68 @@ pub fn send_console_line(line: &str) -> HALResult<()> {
     if v != 0 { Err(HALError(v)) } else { Ok(()) }
 }
 
+/// Makes a CString out of arbitrary text, replacing any interior nul bytes.
+fn lossy_cstring(s: String) -> CString {
+    CString::new(s.replace('\0', "\u{FFFD}")).unwrap_or_default()
+}
+
+// Error codes from `hal/Errors.h`. Only `HAL_`-prefixed codes make it through the bindgen
+// allowlist, so the ones used by this crate are mirrored here.
+pub(crate) const NO_AVAILABLE_RESOURCES: i32 = -104;
+pub(crate) const PARAMETER_OUT_OF_RANGE: i32 = -1028;
+
+/// Reports a failure to spawn one of this crate's background threads to the DS, and turns it
+/// into `NO_AVAILABLE_RESOURCES` for the constructor to return.
+pub(crate) fn thread_spawn_error(name: &str, error: std::io::Error) -> HALError {
+    send_error(NO_AVAILABLE_RESOURCES, &lossy_cstring(format!("failed to spawn {name} thread: {error}"))).ok();
+    HALError(NO_AVAILABLE_RESOURCES)
+}
+
+/// Installs a panic hook that reports panics to the driver station.
+///
+/// The panic message is sent as a DS error, with the panic location in the location field
+/// and a captured backtrace in the call stack field.
+///
+/// If the panic will end the process, the hooks registered with
+/// [`crate::extensions::add_shutdown_hook`] are then run (each only once). That is the case when
+/// building with `panic = "abort"`, or when the panic is on the main thread or the robot thread
+/// started by [`crate::main_loop::run_with_main`]. Panics on other threads may still be caught,
+/// so they leave the hooks for the real shutdown. A hook that panics here aborts the process,
+/// as panics can't be caught inside a panic hook.
+///
+/// The previously installed hook is called last, so the usual stderr output is kept.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = if let Some(s) = payload.downcast_ref::<&str>() {
+            *s
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.as_str()
+        } else {
+            "Box<dyn Any>"
+        };
+        let thread = std::thread::current();
+        let details = format!("Unhandled panic in thread '{}': {}", thread.name().unwrap_or("<unnamed>"), message);
+        let location = info.location().map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())).unwrap_or_default();
+        let backtrace = Backtrace::force_capture().to_string();
+
+        send_error_with_context(
+            true,
+            -1,
+            &lossy_cstring(details),
+            &lossy_cstring(location),
+            &lossy_cstring(backtrace),
+            false
+        ).ok();
+
+        if cfg!(panic = "abort") || matches!(thread.name(), Some("main" | ROBOT_THREAD_NAME)) {
+            crate::extensions::run_shutdown_hooks();
+        }
+        previous(info);
+    }));
+}
+
 /// Converts an Option<&CStr> into an allocation location pointer.
 /// These are nullable.
 /// 
@@ -1,8 +1,8 @@
-use std::ffi::{c_char, c_void, CStr};
+use std::{ffi::{c_char, c_void, CStr}, panic::AssertUnwindSafe, sync::{Mutex, Once, PoisonError}};
 
 use wpihal_sys::{HAL_LoadExtensions, HAL_LoadOneExtension, HAL_OnShutdown, HAL_RegisterExtension, HAL_RegisterExtensionListener, HAL_SetShowExtensionsNotFoundMessages};
 
-use crate::HAL_rust_wpihal_linkage_trampoline;
+use crate::{error::send_error, HAL_rust_wpihal_linkage_trampoline};
 
 
 pub fn load_one_extension(library: &CStr) -> i32 {
@@ -43,4 +43,52 @@ pub fn on_shutdown(f: fn()) {
     unsafe {
         HAL_OnShutdown(f as *mut c_void, Some(HAL_rust_wpihal_linkage_trampoline));
     }
-}
\ No newline at end of file
+}
+
+/// A boxed shutdown hook, see [`add_shutdown_hook`].
+pub type ShutdownHook = Box<dyn FnOnce() + Send>;
+
+static SHUTDOWN_HOOKS: Mutex<Vec<ShutdownHook>> = Mutex::new(Vec::new());
+static SHUTDOWN_HOOKS_REGISTERED: Once = Once::new();
+
+/// Registers a hook to run when the HAL shuts down.
+///
+/// Unlike [`on_shutdown`], hooks can capture state (a log to flush, motors to neutralize...).
+/// Hooks run in reverse registration order, each at most once, from whichever comes first of
+/// [`crate::shutdown`], [`run_shutdown_hooks`], the robot program exiting in
+/// [`crate::robot::start_robot`], a fatal panic (see [`crate::error::install_panic_hook`]), or
+/// Ctrl-C/SIGTERM once [`install_signal_handlers`] has been called.
+/// A panicking hook doesn't stop the rest from running.
+pub fn add_shutdown_hook(hook: ShutdownHook) {
+    SHUTDOWN_HOOKS_REGISTERED.call_once(|| {
+        unsafe { HAL_OnShutdown(core::ptr::null_mut(), Some(HAL_rust_wpihal_shutdown_hooks_callback)); }
+    });
+    SHUTDOWN_HOOKS.lock().unwrap_or_else(PoisonError::into_inner).push(hook);
+}
+
+/// Makes Ctrl-C and SIGTERM (or the console closing, on Windows) run the shutdown hooks and
+/// exit with status 130, so desktop simulation gets to clean up when stopped.
+///
+/// This claims the process-wide `ctrlc` handler, so it fails if one is already installed,
+/// and installing another one afterwards will fail instead.
+pub fn install_signal_handlers() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        run_shutdown_hooks();
+        std::process::exit(130);
+    })
+}
+
+/// Runs and removes all hooks registered with [`add_shutdown_hook`], most recent first.
+pub fn run_shutdown_hooks() {
+    let hooks = core::mem::take(&mut *SHUTDOWN_HOOKS.lock().unwrap_or_else(PoisonError::into_inner));
+    for hook in hooks.into_iter().rev() {
+        if std::panic::catch_unwind(AssertUnwindSafe(hook)).is_err() {
+            send_error(-1, c"a shutdown hook panicked").ok();
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+unsafe extern "C" fn HAL_rust_wpihal_shutdown_hooks_callback(_param: *mut c_void) {
+    run_shutdown_hooks();
+}
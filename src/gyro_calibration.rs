@@ -0,0 +1,205 @@
+use std::{ffi::CStr, time::Duration};
+
+use crate::{analog_accumulator::AnalogAccumulator, analog_gyro::AnalogGyro, analog_input::AnalogInput, error::HALResult, get_fpga_time, sim_device::{SimDevice, SimValue, SimValueDirection}, time::FpgaInstant, value::HALValue};
+
+/// Where a [`GyroCalibrator`] is in its calibration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CalibrationState {
+    /// Waiting for the robot to be disabled before sampling.
+    Waiting,
+    /// Accumulating samples since `started`.
+    Sampling { started: FpgaInstant },
+    /// Calibration was applied to the gyro.
+    Calibrated { center: i32, offset: f64 },
+}
+
+impl CalibrationState {
+    fn sim_index(&self) -> i32 {
+        match self {
+            CalibrationState::Waiting => 0,
+            CalibrationState::Sampling { .. } => 1,
+            CalibrationState::Calibrated { .. } => 2,
+        }
+    }
+}
+
+struct SimValues {
+    _device: SimDevice,
+    state: SimValue,
+    center: SimValue,
+    offset: SimValue,
+}
+
+/// Calibrates an [`AnalogGyro`] a little at a time instead of blocking for five seconds like
+/// [`AnalogGyro::calibrate`].
+///
+/// Call [`GyroCalibrator::update`] every loop. Samples are only taken while the robot is disabled;
+/// if it gets enabled partway through, sampling starts over the next time it is disabled.
+/// Once enough time has been sampled, the center and offset are applied with
+/// [`AnalogGyro::set_parameters`] and the gyro is reset.
+///
+/// With drift tracking enabled, the offset keeps being re-estimated whenever the caller says the
+/// robot is stationary. Note that the HAL applies the offset to everything accumulated since the
+/// last reset, so each correction also nudges the current angle slightly.
+pub struct GyroCalibrator<'a> {
+    accumulator: AnalogAccumulator<'a>,
+    volts_per_degree_per_second: f64,
+    sample_time: Duration,
+    state: CalibrationState,
+    drift_window: Option<Duration>,
+    drift_gain: f64,
+    drift_start: Option<(FpgaInstant, i64, i64)>,
+    sim: Option<SimValues>,
+}
+
+impl<'a> GyroCalibrator<'a> {
+    /// How long the HAL's blocking calibration samples for.
+    pub const DEFAULT_SAMPLE_TIME: Duration = Duration::from_secs(5);
+
+    /// * `input` - the gyro's analog input, which must be accumulator capable
+    /// * `volts_per_degree_per_second` - the gyro's scale, e.g. 0.007 for the KOP gyro
+    pub fn new(input: &'a AnalogInput, volts_per_degree_per_second: f64) -> HALResult<Self> {
+        Ok(Self {
+            accumulator: AnalogAccumulator::initialize(input)?,
+            volts_per_degree_per_second,
+            sample_time: Self::DEFAULT_SAMPLE_TIME,
+            state: CalibrationState::Waiting,
+            drift_window: None,
+            drift_gain: 0.1,
+            drift_start: None,
+            sim: None,
+        })
+    }
+
+    pub fn set_sample_time(&mut self, sample_time: Duration) {
+        self.sample_time = sample_time;
+    }
+
+    /// Enables drift re-estimation over windows of `window` while stationary.
+    /// Each window moves the offset `gain` (0 to 1) of the way toward the new estimate.
+    pub fn enable_drift_tracking(&mut self, window: Duration, gain: f64) {
+        self.drift_window = Some(window);
+        self.drift_gain = gain.clamp(0.0, 1.0);
+        self.drift_start = None;
+    }
+
+    pub fn disable_drift_tracking(&mut self) {
+        self.drift_window = None;
+        self.drift_start = None;
+    }
+
+    /// Publishes the calibration state, center and offset on a sim device named `name`.
+    /// Does nothing outside of simulation.
+    pub fn attach_sim_device(&mut self, name: &CStr) {
+        let Some(device) = SimDevice::new(name) else {
+            return;
+        };
+        let state = device.create_enum(c"state", SimValueDirection::Output, &[c"waiting", c"sampling", c"calibrated"], self.state.sim_index() as usize);
+        let center = device.create_sim_value(c"center", SimValueDirection::Output, &HALValue::Int(0));
+        let offset = device.create_sim_value(c"offset", SimValueDirection::Output, &HALValue::Double(0.0));
+        if let (Some(state), Some(center), Some(offset)) = (state, center, offset) {
+            self.sim = Some(SimValues { _device: device, state, center, offset });
+            self.publish();
+        }
+    }
+
+    pub fn state(&self) -> CalibrationState {
+        self.state
+    }
+
+    pub fn is_calibrated(&self) -> bool {
+        matches!(self.state, CalibrationState::Calibrated { .. })
+    }
+
+    /// Throws away the current calibration and starts again.
+    pub fn restart(&mut self) {
+        self.state = CalibrationState::Waiting;
+        self.drift_start = None;
+        self.publish();
+    }
+
+    /// Advances the calibration.
+    ///
+    /// * `disabled` - whether the robot is disabled; calibration samples are only taken then
+    /// * `stationary` - whether the robot is known not to be turning, for drift tracking
+    pub fn update(&mut self, gyro: &mut AnalogGyro, disabled: bool, stationary: bool) -> HALResult<CalibrationState> {
+        match self.state {
+            CalibrationState::Waiting if disabled => {
+                // accumulate raw values, so their mean is the center
+                self.accumulator.set_center(0)?;
+                self.accumulator.set_deadband(0)?;
+                self.accumulator.reset()?;
+                self.state = CalibrationState::Sampling { started: get_fpga_time()? };
+            }
+            CalibrationState::Waiting => {}
+            CalibrationState::Sampling { .. } if !disabled => {
+                self.state = CalibrationState::Waiting;
+            }
+            CalibrationState::Sampling { started } => {
+                if get_fpga_time()? - started >= self.sample_time {
+                    let (value, count) = self.accumulator.get_output()?;
+                    if count > 0 {
+                        let average = value as f64 / count as f64;
+                        let center = average.round() as i32;
+                        let offset = average - center as f64;
+                        gyro.set_parameters(self.volts_per_degree_per_second, offset, center)?;
+                        gyro.reset()?;
+                        self.state = CalibrationState::Calibrated { center, offset };
+                        self.drift_start = None;
+                    } else {
+                        self.state = CalibrationState::Waiting;
+                    }
+                }
+            }
+            CalibrationState::Calibrated { center, offset } => {
+                if let Some(offset) = self.track_drift(stationary, offset)? {
+                    gyro.set_parameters(self.volts_per_degree_per_second, offset, center)?;
+                    self.state = CalibrationState::Calibrated { center, offset };
+                }
+            }
+        }
+        self.publish();
+        Ok(self.state)
+    }
+
+    /// Returns a new offset once a full stationary window has been measured.
+    fn track_drift(&mut self, stationary: bool, offset: f64) -> HALResult<Option<f64>> {
+        let Some(window) = self.drift_window else {
+            return Ok(None);
+        };
+        if !stationary {
+            self.drift_start = None;
+            return Ok(None);
+        }
+
+        let now = get_fpga_time()?;
+        let (value, count) = self.accumulator.get_output()?;
+        let Some((start, start_value, start_count)) = self.drift_start else {
+            self.drift_start = Some((now, value, count));
+            return Ok(None);
+        };
+        if now - start < window {
+            return Ok(None);
+        }
+        self.drift_start = Some((now, value, count));
+        if count <= start_count {
+            // the accumulator was reset in the meantime
+            return Ok(None);
+        }
+
+        // while stationary, the mean of the centered samples is exactly the offset
+        let measured = (value - start_value) as f64 / (count - start_count) as f64;
+        Ok(Some(offset + (measured - offset) * self.drift_gain))
+    }
+
+    fn publish(&self) {
+        let Some(sim) = &self.sim else {
+            return;
+        };
+        sim.state.set(&HALValue::Enum(self.state.sim_index()));
+        if let CalibrationState::Calibrated { center, offset } = self.state {
+            sim.center.set(&HALValue::Int(center));
+            sim.offset.set(&HALValue::Double(offset));
+        }
+    }
+}
@@ -1,13 +1,20 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, thread::JoinHandle};
+
 use wpihal_sys::{HAL_CleanInterrupts, HAL_InitializeInterrupts, HAL_InterruptHandle, HAL_ReadInterruptFallingTimestamp, HAL_ReadInterruptRisingTimestamp, HAL_ReleaseWaitingInterrupt, HAL_RequestInterrupts, HAL_SetInterruptUpSourceEdge, HAL_WaitForInterrupt, HAL_WaitForMultipleInterrupts};
 
-use crate::{dio::DigitalSource, error::HALResult, hal_call};
+use crate::{dio::DigitalSource, error::{send_error, thread_spawn_error, HALResult}, hal_call, time::FpgaInstant, Handle};
+
 
+/// Bits of a [`Interrupts::wait_for_interrupt`] result that are set for a rising edge.
+pub const RISING_EDGE_MASK: i64 = 0xFF;
+/// Bits of a [`Interrupts::wait_for_interrupt`] result that are set for a falling edge.
+pub const FALLING_EDGE_MASK: i64 = 0xFF00;
 
-pub struct Interrupts(HAL_InterruptHandle);
+pub struct Interrupts<'a>(HAL_InterruptHandle, Option<DigitalSource<'a>>);
 
-impl Interrupts {
+impl<'a> Interrupts<'a> {
     pub fn initialize() -> HALResult<Self> {
-        Ok(Self(hal_call!(HAL_InitializeInterrupts())?))
+        Ok(Self(hal_call!(HAL_InitializeInterrupts())?, None))
     }
 
     pub fn wait_for_interrupt(&self, timeout: f64, ignore_previous: bool) -> HALResult<i64> {
@@ -18,16 +25,19 @@ impl Interrupts {
         hal_call!(HAL_WaitForMultipleInterrupts(self.0, mask as i64, timeout, ignore_previous as i32))
     }
 
-    pub fn read_interrupt_rising_timestamp(&self) -> HALResult<i64> {
-        hal_call!(HAL_ReadInterruptRisingTimestamp(self.0))
+    pub fn read_interrupt_rising_timestamp(&self) -> HALResult<FpgaInstant> {
+        Ok(FpgaInstant::from_micros(hal_call!(HAL_ReadInterruptRisingTimestamp(self.0))? as u64))
     }
 
-    pub fn read_interrupt_falling_timestamp(&self) -> HALResult<i64> {
-        hal_call!(HAL_ReadInterruptFallingTimestamp(self.0))
+    pub fn read_interrupt_falling_timestamp(&self) -> HALResult<FpgaInstant> {
+        Ok(FpgaInstant::from_micros(hal_call!(HAL_ReadInterruptFallingTimestamp(self.0))? as u64))
     }
 
-    pub fn request_interrupts(&mut self, digital_source: DigitalSource) -> HALResult<()> {
-        hal_call!(HAL_RequestInterrupts(self.0, digital_source.raw_handle(), digital_source.analog_trigger_type()))
+    /// Attaches the interrupt to a source. The source is borrowed for as long as the interrupt exists.
+    pub fn request_interrupts(&mut self, digital_source: DigitalSource<'a>) -> HALResult<()> {
+        hal_call!(HAL_RequestInterrupts(self.0, digital_source.raw_handle(), digital_source.analog_trigger_type()))?;
+        self.1 = Some(digital_source);
+        Ok(())
     }
 
     pub fn set_interrupt_up_source_edge(&mut self, rising: bool, falling: bool) -> HALResult<()> {
@@ -40,8 +50,128 @@ impl Interrupts {
 
 }
 
-impl Drop for Interrupts {
+impl<'a> Drop for Interrupts<'a> {
     fn drop(&mut self) {
         unsafe { HAL_CleanInterrupts(self.0); }
     }
-}
\ No newline at end of file
+}
+
+impl<'a> Handle<HAL_InterruptHandle> for Interrupts<'a> {
+    unsafe fn raw_handle(&self) -> HAL_InterruptHandle {
+        self.0
+    }
+
+    unsafe fn from_raw_handle(handle: HAL_InterruptHandle) -> Self {
+        Self(handle, None)
+    }
+}
+
+/// Calls a closure from a background thread whenever a digital source sees an edge,
+/// a la WPILib's AsynchronousInterrupt.
+///
+/// The closure gets `(rising, falling, timestamp)`, where the timestamp is that of the rising
+/// edge if there was one, and of the falling edge otherwise.
+/// Dropping this releases the waiting thread and joins it before the interrupt is cleaned up.
+pub struct AsyncInterrupt<'a> {
+    interrupts: Interrupts<'a>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<'a> AsyncInterrupt<'a> {
+    /// How long the background thread waits before checking whether it should stop, in seconds.
+    ///
+    /// Drop releases the wait, but a release that lands while the thread is between waits is
+    /// lost, so this bounds how long dropping can block.
+    const WAIT_TIMEOUT: f64 = 0.1;
+
+    pub fn new(source: DigitalSource<'a>, rising: bool, falling: bool, mut callback: impl FnMut(bool, bool, FpgaInstant) + Send + 'static) -> HALResult<Self> {
+        Self::spawn(source, rising, falling, move |result, interrupts| {
+            let rising = result & RISING_EDGE_MASK != 0;
+            let timestamp = if rising {
+                interrupts.read_interrupt_rising_timestamp()
+            } else {
+                interrupts.read_interrupt_falling_timestamp()
+            };
+            if let Ok(timestamp) = timestamp {
+                callback(rising, result & FALLING_EDGE_MASK != 0, timestamp);
+            }
+        })
+    }
+
+    /// Like [`AsyncInterrupt::new`], but the closure gets the timestamp of each edge that fired,
+    /// so that a rising and a falling edge reported together can be told apart.
+    pub fn with_edge_timestamps(source: DigitalSource<'a>, rising: bool, falling: bool, mut callback: impl FnMut(Option<FpgaInstant>, Option<FpgaInstant>) + Send + 'static) -> HALResult<Self> {
+        Self::spawn(source, rising, falling, move |result, interrupts| {
+            let rising = if result & RISING_EDGE_MASK != 0 { interrupts.read_interrupt_rising_timestamp().ok() } else { None };
+            let falling = if result & FALLING_EDGE_MASK != 0 { interrupts.read_interrupt_falling_timestamp().ok() } else { None };
+            if rising.is_some() || falling.is_some() {
+                callback(rising, falling);
+            }
+        })
+    }
+
+    /// Spawns the waiting thread; `on_edge` gets the non-zero wait result.
+    fn spawn(source: DigitalSource<'a>, rising: bool, falling: bool, mut on_edge: impl FnMut(i64, &Interrupts) + Send + 'static) -> HALResult<Self> {
+        let mut interrupts = Interrupts::initialize()?;
+        interrupts.request_interrupts(source)?;
+        interrupts.set_interrupt_up_source_edge(rising, falling)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        // the thread only gets the raw handle; Drop joins it before `interrupts` is cleaned up
+        let handle = unsafe { interrupts.raw_handle() };
+        let timeout = Self::WAIT_TIMEOUT;
+        let thread = {
+            let running = running.clone();
+            std::thread::Builder::new().name("AsyncInterrupt".to_string()).spawn(move || {
+                let interrupts = std::mem::ManuallyDrop::new(unsafe { Interrupts::from_raw_handle(handle) });
+                while running.load(Ordering::Acquire) {
+                    let result = match interrupts.wait_for_interrupt(timeout, false) {
+                        Ok(result) => result,
+                        Err(e) => {
+                            // not worth reporting if we are being dropped anyway
+                            if running.swap(false, Ordering::AcqRel) {
+                                send_error(e.0, c"AsyncInterrupt stopped: waiting for the interrupt failed").ok();
+                            }
+                            break;
+                        }
+                    };
+                    if !running.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if result & (RISING_EDGE_MASK | FALLING_EDGE_MASK) == 0 {
+                        // timed out
+                        continue;
+                    }
+                    on_edge(result, &interrupts);
+                }
+            }).map_err(|e| thread_spawn_error("AsyncInterrupt", e))?
+        };
+
+        Ok(Self { interrupts, running, thread: Some(thread) })
+    }
+
+    /// Whether the callback is still being called. Becomes false if waiting for the interrupt
+    /// fails, which is also reported to the DS.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    pub fn read_rising_timestamp(&self) -> HALResult<FpgaInstant> {
+        self.interrupts.read_interrupt_rising_timestamp()
+    }
+
+    pub fn read_falling_timestamp(&self) -> HALResult<FpgaInstant> {
+        self.interrupts.read_interrupt_falling_timestamp()
+    }
+}
+
+impl<'a> Drop for AsyncInterrupt<'a> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        self.interrupts.release_waiting_interrupt().ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
@@ -1,9 +1,12 @@
 use std::{ffi::{c_void, CStr}, time::Duration};
 
 use error::{HALError, HALResult};
+use time::FpgaInstant;
 use wpihal_sys::{HAL_ExpandFPGATime, HAL_GetBrownedOut, HAL_GetComments, HAL_GetCommsDisableCount, HAL_GetFPGAButton, HAL_GetFPGATime, HAL_GetFPGAVersion, HAL_GetLastError, HAL_GetPort, HAL_GetPortWithModule, HAL_GetRSLState, HAL_GetRuntimeType, HAL_GetSerialNumber, HAL_GetSystemActive, HAL_GetSystemClockTicksPerMicrosecond, HAL_GetSystemTimeValid, HAL_GetTeamNumber, HAL_Initialize, HAL_PortHandle, HAL_RuntimeType, HAL_Shutdown, HAL_SimPeriodicAfter, HAL_SimPeriodicBefore, WPI_String};
 use wpiutil::wpistring::WPIString;
 
+pub use wpihal_macros::{robot_main, DMASample};
+
 /// this is the higher level package
 /// i guess
 
@@ -14,34 +17,52 @@ pub mod accelerometer;
 pub mod addressable_led;
 /// analog accumulator
 pub mod analog_accumulator;
+/// analog absolute encoders
+pub mod analog_encoder;
 /// analog gyro (my condolences)
 pub mod analog_gyro;
 /// analog input
 pub mod analog_input;
 /// analog output
 pub mod analog_output;
+/// analog potentiometers
+pub mod analog_potentiometer;
 /// analog trigger
 pub mod analog_trigger;
+/// async notifiers
+pub mod async_notifier;
 /// can bus
 pub mod can;
 /// can api
 pub mod can_api;
+/// xbox and playstation controller layouts
+pub mod controller;
 /// counter
 pub mod counter;
 /// ctre pcm
 pub mod ctre_pcm;
+/// one-shot deadlines on a shared notifier
+pub mod deadline_scheduler;
 /// digital i/o
 pub mod dio;
 /// DMA
 pub mod dma;
+/// DMA sample history
+pub mod dma_history;
 /// driver station data
 pub mod driver_station;
 /// duty cycle input
 pub mod duty_cycle;
+/// absolute duty cycle encoders
+pub mod duty_cycle_encoder;
 /// quadrature encoders
 pub mod encoder;
+/// encoder velocity estimation
+pub mod encoder_velocity;
 /// HAL extensions
 pub mod extensions;
+/// non-blocking analog gyro calibration
+pub mod gyro_calibration;
 /// I2C transactions (may freeze your rio)
 pub mod i2c;
 /// interrupts
@@ -50,20 +71,32 @@ pub mod interrupts;
 pub mod leds;
 /// main loop management
 pub mod main_loop;
+/// match phase tracking
+pub mod match_phase;
 /// notifiers
 pub mod notifier;
+/// multi-rate periodic scheduler
+pub mod periodic_scheduler;
 /// ports
 pub mod ports;
 /// power
 pub mod power;
 /// power distribution
 pub mod power_distribution;
+/// pulse width and frequency measurement
+pub mod pulse_meter;
 /// Threads
 pub mod threads;
+/// FPGA timestamps
+pub mod time;
 /// PWM output
 pub mod pwm;
+/// PWM motor controllers and servos
+pub mod pwm_motor_controller;
 /// relays
 pub mod relay;
+/// TimedRobot-style robot loop
+pub mod robot;
 /// rev pneumatic hub
 pub mod rev_ph;
 /// serial ports
@@ -72,10 +105,14 @@ pub mod serial_port;
 pub mod sim_device;
 /// SPI
 pub mod spi;
+/// ultrasonic range finders
+pub mod ultrasonic;
 /// usage reporting
 pub mod usage_reporting;
 /// HALValue
 pub mod value;
+/// loop overrun watchdog
+pub mod watchdog;
 
 /*
 dma
@@ -187,16 +224,18 @@ pub fn get_port_with_module(module: i32, channel: i32) -> HAL_PortHandle {
     unsafe { HAL_GetPortWithModule(module, channel) }
 }
 
-pub fn get_fpga_time() -> HALResult<u64> {
-    hal_call!(HAL_GetFPGATime())
+pub fn get_fpga_time() -> HALResult<FpgaInstant> {
+    Ok(FpgaInstant::from_micros(hal_call!(HAL_GetFPGATime())?))
 }
 
 pub fn get_fpga_duration() -> HALResult<Duration> {
-    Ok(Duration::from_micros(get_fpga_time()?))
+    Ok(get_fpga_time()?.since_start())
 }
 
-pub fn expand_fpga_time(lower: u32) -> HALResult<u64> {
-    hal_call!(HAL_ExpandFPGATime(lower))
+/// Expands the lower 32 bits of an FPGA timestamp into a full one,
+/// assuming it is from within the last ~71 minutes.
+pub fn expand_fpga_time(lower: u32) -> HALResult<FpgaInstant> {
+    Ok(FpgaInstant::from_micros(hal_call!(HAL_ExpandFPGATime(lower))?))
 }
 
 pub fn get_rsl_state() -> HALResult<bool> {
@@ -21,4 +21,42 @@ pub fn run_main() {
 
 pub fn exit_main() {
     unsafe { HAL_ExitMain(); }
-}
\ No newline at end of file
+}
+
+/// Name of the thread [`run_with_main`] runs the robot on.
+pub(crate) const ROBOT_THREAD_NAME: &str = "robot";
+
+/// Calls [`exit_main`] when dropped, including during unwinding.
+struct ExitMainGuard;
+
+impl Drop for ExitMainGuard {
+    fn drop(&mut self) {
+        exit_main();
+    }
+}
+
+/// Runs `robot` while letting a registered main-loop owner (e.g. a HALSim GUI extension)
+/// keep the calling thread.
+///
+/// If [`has_main`] is true, `robot` is spawned on a worker thread and [`run_main`] is
+/// called on this thread; [`exit_main`] is called once `robot` returns or panics.
+/// Otherwise `robot` just runs here.
+///
+/// A panic in `robot` is resumed on the calling thread after the main loop exits.
+pub fn run_with_main(robot: impl FnOnce() + Send + 'static) {
+    if !has_main() {
+        robot();
+        return;
+    }
+
+    let worker = std::thread::Builder::new().name(ROBOT_THREAD_NAME.to_string()).spawn(move || {
+        let _guard = ExitMainGuard;
+        robot();
+    }).expect("failed to spawn robot thread");
+
+    run_main();
+
+    if let Err(panic) = worker.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
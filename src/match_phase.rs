@@ -0,0 +1,277 @@
+use crate::{driver_station::{get_control_word, get_match_time, ControlWord}, error::HALResult};
+
+/// Default teleop period length in seconds, used when the match timer counts up.
+pub const DEFAULT_TELEOP_LENGTH: f64 = 135.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchPhase {
+    /// Nothing has been enabled yet.
+    PreMatch,
+    Autonomous,
+    /// Disabled between the end of autonomous and the start of teleop.
+    AutoTeleopGap,
+    Teleop,
+    /// The last `endgame_threshold` seconds of teleop.
+    Endgame,
+    /// Disabled after teleop.
+    PostMatch,
+}
+
+/// Which way [`get_match_time`] is running during teleop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchTimeDirection {
+    Unknown,
+    /// FMS and DS practice mode: time remaining in the period.
+    CountingDown,
+    /// Plain DS teleop/autonomous: time elapsed in the period.
+    CountingUp,
+}
+
+/// Emitted whenever the tracked phase changes, including when the endgame threshold is crossed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MatchPhaseEvent {
+    pub from: MatchPhase,
+    pub to: MatchPhase,
+    /// The raw match time at the moment of the transition.
+    pub match_time: f64,
+}
+
+/// Tracks match phases from the control word and [`get_match_time`].
+///
+/// Call [`MatchPhaseTracker::update`] once per loop. Endgame is sticky: once entered it
+/// is held until the robot is disabled, so the integer steps the FMS sends can't make
+/// it flap.
+#[derive(Debug, Clone)]
+pub struct MatchPhaseTracker {
+    endgame_threshold: f64,
+    teleop_length: f64,
+    phase: MatchPhase,
+    direction: MatchTimeDirection,
+    last_teleop_time: Option<f64>,
+    remaining: Option<f64>,
+}
+
+impl MatchPhaseTracker {
+    /// * `endgame_threshold` - seconds remaining in teleop at which endgame starts
+    pub fn new(endgame_threshold: f64) -> Self {
+        Self {
+            endgame_threshold,
+            teleop_length: DEFAULT_TELEOP_LENGTH,
+            phase: MatchPhase::PreMatch,
+            direction: MatchTimeDirection::Unknown,
+            last_teleop_time: None,
+            remaining: None,
+        }
+    }
+
+    /// Sets the teleop length used to compute time remaining when the match timer counts up.
+    pub fn set_teleop_length(&mut self, seconds: f64) {
+        self.teleop_length = seconds;
+    }
+
+    pub fn phase(&self) -> MatchPhase {
+        self.phase
+    }
+
+    pub fn direction(&self) -> MatchTimeDirection {
+        self.direction
+    }
+
+    pub fn is_endgame(&self) -> bool {
+        self.phase == MatchPhase::Endgame
+    }
+
+    /// Seconds left in teleop, if currently in teleop and the match time is known.
+    pub fn remaining_teleop_time(&self) -> Option<f64> {
+        self.remaining
+    }
+
+    /// Forgets the current match and goes back to [`MatchPhase::PreMatch`].
+    pub fn reset(&mut self) {
+        self.phase = MatchPhase::PreMatch;
+        self.direction = MatchTimeDirection::Unknown;
+        self.last_teleop_time = None;
+        self.remaining = None;
+    }
+
+    /// Reads the control word and match time from the HAL and updates the phase.
+    pub fn update(&mut self) -> HALResult<Option<MatchPhaseEvent>> {
+        let word = get_control_word()?;
+        let match_time = get_match_time()?;
+        Ok(self.update_with(word, match_time))
+    }
+
+    /// Updates the phase from an already-fetched control word and match time.
+    pub fn update_with(&mut self, word: ControlWord, match_time: f64) -> Option<MatchPhaseEvent> {
+        let next = if word.enabled() && !word.test() {
+            if word.autonomous() {
+                self.leave_teleop();
+                MatchPhase::Autonomous
+            } else {
+                self.teleop_phase(word, match_time)
+            }
+        } else if word.enabled() {
+            // test mode isn't part of a match; hold whatever we had
+            self.phase
+        } else {
+            self.leave_teleop();
+            match self.phase {
+                MatchPhase::PreMatch => MatchPhase::PreMatch,
+                MatchPhase::Autonomous | MatchPhase::AutoTeleopGap => MatchPhase::AutoTeleopGap,
+                MatchPhase::Teleop | MatchPhase::Endgame | MatchPhase::PostMatch => MatchPhase::PostMatch,
+            }
+        };
+
+        if next == self.phase {
+            return None;
+        }
+        let event = MatchPhaseEvent { from: self.phase, to: next, match_time };
+        self.phase = next;
+        Some(event)
+    }
+
+    fn teleop_phase(&mut self, word: ControlWord, match_time: f64) -> MatchPhase {
+        if match_time < 0.0 {
+            // no match time available; we can't place endgame
+            self.remaining = None;
+            return if self.phase == MatchPhase::Endgame { MatchPhase::Endgame } else { MatchPhase::Teleop };
+        }
+
+        self.direction = if word.fms_attached() {
+            MatchTimeDirection::CountingDown
+        } else {
+            match (self.direction, self.last_teleop_time) {
+                (_, Some(last)) if match_time < last => MatchTimeDirection::CountingDown,
+                (_, Some(last)) if match_time > last => MatchTimeDirection::CountingUp,
+                (MatchTimeDirection::Unknown, None) if match_time > self.teleop_length / 2.0 => MatchTimeDirection::CountingDown,
+                (MatchTimeDirection::Unknown, None) => MatchTimeDirection::CountingUp,
+                (direction, _) => direction,
+            }
+        };
+        self.last_teleop_time = Some(match_time);
+
+        let remaining = match self.direction {
+            MatchTimeDirection::CountingUp => self.teleop_length - match_time,
+            _ => match_time,
+        };
+        self.remaining = Some(remaining.max(0.0));
+
+        if self.phase == MatchPhase::Endgame || remaining <= self.endgame_threshold {
+            MatchPhase::Endgame
+        } else {
+            MatchPhase::Teleop
+        }
+    }
+
+    fn leave_teleop(&mut self) {
+        self.direction = MatchTimeDirection::Unknown;
+        self.last_teleop_time = None;
+        self.remaining = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISABLED: ControlWord = ControlWord(0);
+    const TELEOP: ControlWord = ControlWord(0b1);
+    const AUTONOMOUS: ControlWord = ControlWord(0b11);
+    const TEST: ControlWord = ControlWord(0b101);
+    const FMS: u32 = 0b10000;
+
+    fn fms(word: ControlWord) -> ControlWord {
+        ControlWord(word.0 | FMS)
+    }
+
+    fn transition(tracker: &mut MatchPhaseTracker, word: ControlWord, match_time: f64) -> Option<(MatchPhase, MatchPhase)> {
+        tracker.update_with(word, match_time).map(|event| (event.from, event.to))
+    }
+
+    #[test]
+    fn fms_match() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        assert_eq!(transition(&mut tracker, fms(DISABLED), -1.0), None);
+        assert_eq!(transition(&mut tracker, fms(AUTONOMOUS), 15.0), Some((MatchPhase::PreMatch, MatchPhase::Autonomous)));
+        assert_eq!(transition(&mut tracker, fms(DISABLED), 0.0), Some((MatchPhase::Autonomous, MatchPhase::AutoTeleopGap)));
+        assert_eq!(transition(&mut tracker, fms(TELEOP), 135.0), Some((MatchPhase::AutoTeleopGap, MatchPhase::Teleop)));
+        assert_eq!(tracker.direction(), MatchTimeDirection::CountingDown);
+        assert_eq!(tracker.remaining_teleop_time(), Some(135.0));
+        assert_eq!(transition(&mut tracker, fms(TELEOP), 31.0), None);
+        assert_eq!(transition(&mut tracker, fms(TELEOP), 30.0), Some((MatchPhase::Teleop, MatchPhase::Endgame)));
+        assert_eq!(transition(&mut tracker, fms(DISABLED), 0.0), Some((MatchPhase::Endgame, MatchPhase::PostMatch)));
+    }
+
+    #[test]
+    fn counting_up_without_fms() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        assert_eq!(transition(&mut tracker, TELEOP, 0.0), Some((MatchPhase::PreMatch, MatchPhase::Teleop)));
+        assert_eq!(tracker.direction(), MatchTimeDirection::CountingUp);
+        assert_eq!(tracker.remaining_teleop_time(), Some(DEFAULT_TELEOP_LENGTH));
+        assert_eq!(transition(&mut tracker, TELEOP, 104.0), None);
+        assert_eq!(transition(&mut tracker, TELEOP, 105.0), Some((MatchPhase::Teleop, MatchPhase::Endgame)));
+    }
+
+    #[test]
+    fn counting_down_without_fms() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        // a late first sample is taken as elapsed time until the next one says otherwise
+        assert_eq!(transition(&mut tracker, TELEOP, 40.0), Some((MatchPhase::PreMatch, MatchPhase::Teleop)));
+        assert_eq!(tracker.direction(), MatchTimeDirection::CountingUp);
+        assert_eq!(transition(&mut tracker, TELEOP, 29.0), Some((MatchPhase::Teleop, MatchPhase::Endgame)));
+        assert_eq!(tracker.direction(), MatchTimeDirection::CountingDown);
+        assert_eq!(tracker.remaining_teleop_time(), Some(29.0));
+    }
+
+    #[test]
+    fn direction_guessed_from_first_sample() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        transition(&mut tracker, TELEOP, 120.0);
+        assert_eq!(tracker.direction(), MatchTimeDirection::CountingDown);
+        // an unchanged time keeps the direction
+        transition(&mut tracker, TELEOP, 120.0);
+        assert_eq!(tracker.direction(), MatchTimeDirection::CountingDown);
+    }
+
+    #[test]
+    fn endgame_is_sticky() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        transition(&mut tracker, fms(TELEOP), 30.0);
+        assert!(tracker.is_endgame());
+        assert_eq!(transition(&mut tracker, fms(TELEOP), 31.0), None);
+        assert_eq!(transition(&mut tracker, fms(TELEOP), -1.0), None);
+        assert!(tracker.is_endgame());
+        assert_eq!(tracker.remaining_teleop_time(), None);
+    }
+
+    #[test]
+    fn disable_resets_teleop_tracking() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        transition(&mut tracker, fms(TELEOP), 10.0);
+        assert!(tracker.is_endgame());
+        assert_eq!(transition(&mut tracker, DISABLED, -1.0), Some((MatchPhase::Endgame, MatchPhase::PostMatch)));
+        assert_eq!(tracker.direction(), MatchTimeDirection::Unknown);
+        assert_eq!(tracker.remaining_teleop_time(), None);
+        // endgame doesn't carry over into the next enable
+        assert_eq!(transition(&mut tracker, TELEOP, 0.0), Some((MatchPhase::PostMatch, MatchPhase::Teleop)));
+        assert_eq!(tracker.direction(), MatchTimeDirection::CountingUp);
+    }
+
+    #[test]
+    fn test_mode_holds_phase() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        transition(&mut tracker, AUTONOMOUS, 15.0);
+        assert_eq!(transition(&mut tracker, TEST, 0.0), None);
+        assert_eq!(tracker.phase(), MatchPhase::Autonomous);
+    }
+
+    #[test]
+    fn reset_returns_to_prematch() {
+        let mut tracker = MatchPhaseTracker::new(30.0);
+        transition(&mut tracker, fms(TELEOP), 100.0);
+        tracker.reset();
+        assert_eq!(tracker.phase(), MatchPhase::PreMatch);
+        assert_eq!(tracker.direction(), MatchTimeDirection::Unknown);
+        assert_eq!(tracker.remaining_teleop_time(), None);
+    }
+}
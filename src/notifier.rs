@@ -2,9 +2,13 @@ use std::ffi::CStr;
 
 use wpihal_sys::{HAL_CancelNotifierAlarm, HAL_CleanNotifier, HAL_InitializeNotifier, HAL_NotifierHandle, HAL_SetNotifierName, HAL_SetNotifierThreadPriority, HAL_StopNotifier, HAL_UpdateNotifierAlarm, HAL_WaitForNotifierAlarm};
 
-use crate::{error::HALResult, hal_call};
+use crate::{error::HALResult, hal_call, time::FpgaInstant};
 
 
+/// HAL notifier.
+///
+/// The alarm and stop functions are thread-safe in the HAL, so they only need `&self`;
+/// this lets one thread block in [`Notifier::wait_for_alarm`] while another re-arms or stops it.
 pub struct Notifier(HAL_NotifierHandle);
 
 impl Notifier {
@@ -20,20 +24,25 @@ impl Notifier {
         hal_call!(HAL_SetNotifierName(self.0, name.as_ptr()))
     }
 
-    pub fn stop(&mut self) -> HALResult<()> {
+    pub fn stop(&self) -> HALResult<()> {
         hal_call!(HAL_StopNotifier(self.0))
     }
 
-    pub fn update_alarm(&mut self, trigger_time: u64) -> HALResult<()> {
-        hal_call!(HAL_UpdateNotifierAlarm(self.0, trigger_time))
+    pub fn update_alarm(&self, trigger_time: FpgaInstant) -> HALResult<()> {
+        hal_call!(HAL_UpdateNotifierAlarm(self.0, trigger_time.as_micros()))
     }
 
-    pub fn cancel_alarm(&mut self) -> HALResult<()> {
+    pub fn cancel_alarm(&self) -> HALResult<()> {
         hal_call!(HAL_CancelNotifierAlarm(self.0))
     }
 
-    pub fn wait_for_alarm(&self) -> HALResult<u64> {
-        hal_call!(HAL_WaitForNotifierAlarm(self.0))
+    /// Blocks until the alarm fires, returning the FPGA time it fired at.
+    /// Returns `None` if the notifier was stopped.
+    pub fn wait_for_alarm(&self) -> HALResult<Option<FpgaInstant>> {
+        match hal_call!(HAL_WaitForNotifierAlarm(self.0))? {
+            0 => Ok(None),
+            time => Ok(Some(FpgaInstant::from_micros(time)))
+        }
     }
 
 }
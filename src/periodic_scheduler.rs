@@ -0,0 +1,118 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{error::HALResult, get_fpga_time, notifier::Notifier, time::{next_deadline, FpgaInstant}};
+
+/// Identifies a task added to a [`PeriodicScheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskId(usize);
+
+/// Per-task run and overrun counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TaskStats {
+    /// Number of times the callback has run.
+    pub runs: u64,
+    /// Total cycles skipped because this task (or one ahead of it) ran past its next deadline.
+    pub skipped_cycles: u64,
+    /// Cycles skipped after the most recent run.
+    pub last_skipped: u64,
+}
+
+struct Task {
+    period: Duration,
+    expiration: FpgaInstant,
+    callback: Box<dyn FnMut() + Send>,
+    stats: TaskStats,
+}
+
+/// Stops a running [`PeriodicScheduler`] from another thread.
+#[derive(Clone)]
+pub struct SchedulerStopHandle(Arc<Notifier>);
+
+impl SchedulerStopHandle {
+    pub fn stop(&self) -> HALResult<()> {
+        self.0.stop()
+    }
+}
+
+/// Runs many periodic closures with different periods and phase offsets off one HAL notifier.
+///
+/// Deadlines are absolute FPGA times computed from a common start time, so tasks don't drift
+/// relative to each other or to the clock. A task that falls behind skips the missed cycles
+/// instead of running them back to back, and the skip is recorded in its [`TaskStats`].
+pub struct PeriodicScheduler {
+    notifier: Arc<Notifier>,
+    start: FpgaInstant,
+    tasks: Vec<Task>,
+}
+
+impl PeriodicScheduler {
+    pub fn new() -> HALResult<Self> {
+        let mut notifier = Notifier::initialize()?;
+        notifier.set_name(c"PeriodicScheduler")?;
+        Ok(Self {
+            notifier: Arc::new(notifier),
+            start: get_fpga_time()?,
+            tasks: Vec::new(),
+        })
+    }
+
+    /// Adds a task that runs every `period`, offset by `offset` from the scheduler's start time.
+    pub fn add(&mut self, period: Duration, offset: Duration, callback: impl FnMut() + Send + 'static) -> HALResult<TaskId> {
+        let period = period.max(Duration::from_micros(1));
+        let now = get_fpga_time()?;
+        let base = self.start + offset;
+        let expiration = if now >= base {
+            // pretend the task has been running since `base`, so its phase stays aligned
+            let into_period = (now - base).as_micros() % period.as_micros();
+            now - Duration::from_micros(into_period as u64) + period
+        } else {
+            base
+        };
+
+        self.tasks.push(Task {
+            period,
+            expiration,
+            callback: Box::new(callback),
+            stats: TaskStats::default(),
+        });
+        Ok(TaskId(self.tasks.len() - 1))
+    }
+
+    pub fn stats(&self, task: TaskId) -> Option<TaskStats> {
+        self.tasks.get(task.0).map(|t| t.stats)
+    }
+
+    pub fn stop_handle(&self) -> SchedulerStopHandle {
+        SchedulerStopHandle(self.notifier.clone())
+    }
+
+    /// Runs tasks on the calling thread until stopped through a [`SchedulerStopHandle`].
+    pub fn run(&mut self) -> HALResult<()> {
+        let mut due = Vec::with_capacity(self.tasks.len());
+        loop {
+            let Some(next) = self.tasks.iter().map(|t| t.expiration).min() else {
+                return Ok(());
+            };
+            self.notifier.update_alarm(next)?;
+            let Some(now) = self.notifier.wait_for_alarm()? else {
+                // notifier was stopped
+                return Ok(());
+            };
+
+            due.clear();
+            due.extend((0..self.tasks.len()).filter(|&i| self.tasks[i].expiration <= now));
+            due.sort_by_key(|&i| self.tasks[i].expiration);
+
+            for &i in due.iter() {
+                let task = &mut self.tasks[i];
+                (task.callback)();
+                task.stats.runs += 1;
+
+                let (expiration, skipped) = next_deadline(task.expiration, task.period, get_fpga_time()?);
+                task.expiration = expiration;
+                task.stats.last_skipped = skipped;
+                task.stats.skipped_cycles += skipped;
+            }
+        }
+    }
+}
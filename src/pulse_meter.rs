@@ -0,0 +1,243 @@
+use std::{collections::VecDeque, sync::{Arc, Mutex, MutexGuard, PoisonError}, time::Duration};
+
+use crate::{dio::DigitalSource, error::HALResult, get_fpga_time, interrupts::AsyncInterrupt, time::FpgaInstant};
+
+/// Rolling statistics over the last few pulse widths or periods.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PulseStats {
+    /// Number of samples the statistics were computed from.
+    pub count: usize,
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    /// Standard deviation.
+    pub jitter: Duration,
+}
+
+impl PulseStats {
+    fn compute(samples: &VecDeque<Duration>) -> Option<Self> {
+        let count = samples.len();
+        if count == 0 {
+            return None;
+        }
+        let secs = || samples.iter().map(Duration::as_secs_f64);
+        let mean = secs().sum::<f64>() / count as f64;
+        let variance = secs().map(|s| (s - mean) * (s - mean)).sum::<f64>() / count as f64;
+        Some(Self {
+            count,
+            mean: Duration::from_secs_f64(mean),
+            min: samples.iter().copied().min().unwrap_or_default(),
+            max: samples.iter().copied().max().unwrap_or_default(),
+            jitter: Duration::from_secs_f64(variance.sqrt()),
+        })
+    }
+}
+
+#[derive(Default)]
+struct State {
+    window: usize,
+    last_rise: Option<FpgaInstant>,
+    last_fall: Option<FpgaInstant>,
+    last_edge: Option<FpgaInstant>,
+    widths: VecDeque<Duration>,
+    periods: VecDeque<Duration>,
+}
+
+impl State {
+    fn push(samples: &mut VecDeque<Duration>, window: usize, sample: Duration) {
+        if samples.len() >= window {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    fn rising(&mut self, time: FpgaInstant) {
+        if let Some(rise) = self.last_rise {
+            if time > rise {
+                Self::push(&mut self.periods, self.window, time - rise);
+            }
+        }
+        self.last_rise = Some(time);
+        self.last_edge = Some(time);
+    }
+
+    fn falling(&mut self, time: FpgaInstant) {
+        // only pair with a rise that hasn't already been closed by a fall
+        if let Some(rise) = self.last_rise {
+            if time > rise && !matches!(self.last_fall, Some(fall) if fall >= rise) {
+                Self::push(&mut self.widths, self.window, time - rise);
+            }
+        }
+        self.last_fall = Some(time);
+        self.last_edge = Some(time);
+    }
+}
+
+/// Measures pulse widths (high time) and periods of a digital signal from interrupt edge timestamps.
+///
+/// Useful for PWM sensors at rates the FPGA duty cycle block doesn't handle well, or for
+/// timing beam breaks. Statistics are kept over the last `window` pulses, and the signal is
+/// considered stopped if no edge arrives within the timeout (100 ms by default),
+/// in which case the measurements read as `None`.
+pub struct PulseMeter<'a> {
+    state: Arc<Mutex<State>>,
+    timeout: Duration,
+    _interrupt: AsyncInterrupt<'a>,
+}
+
+impl<'a> PulseMeter<'a> {
+    pub fn new(source: DigitalSource<'a>, window: usize) -> HALResult<Self> {
+        let state = Arc::new(Mutex::new(State { window: window.max(1), ..Default::default() }));
+        let interrupt = {
+            let state = state.clone();
+            AsyncInterrupt::with_edge_timestamps(source, true, true, move |rising, falling| {
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                match (rising, falling) {
+                    (Some(rise), Some(fall)) if fall < rise => {
+                        state.falling(fall);
+                        state.rising(rise);
+                    }
+                    (rise, fall) => {
+                        if let Some(rise) = rise {
+                            state.rising(rise);
+                        }
+                        if let Some(fall) = fall {
+                            state.falling(fall);
+                        }
+                    }
+                }
+            })?
+        };
+        Ok(Self { state, timeout: Duration::from_millis(100), _interrupt: interrupt })
+    }
+
+    /// Sets how long the signal may go without an edge before it counts as stopped.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Whether no edge has been seen within the timeout (or at all).
+    pub fn is_stopped(&self) -> HALResult<bool> {
+        let last_edge = self.lock().last_edge;
+        Ok(match last_edge {
+            Some(edge) => get_fpga_time()? - edge > self.timeout,
+            None => true,
+        })
+    }
+
+    pub fn pulse_width_stats(&self) -> HALResult<Option<PulseStats>> {
+        self.if_running(|state| PulseStats::compute(&state.widths))
+    }
+
+    pub fn period_stats(&self) -> HALResult<Option<PulseStats>> {
+        self.if_running(|state| PulseStats::compute(&state.periods))
+    }
+
+    pub fn last_pulse_width(&self) -> HALResult<Option<Duration>> {
+        self.if_running(|state| state.widths.back().copied())
+    }
+
+    pub fn last_period(&self) -> HALResult<Option<Duration>> {
+        self.if_running(|state| state.periods.back().copied())
+    }
+
+    /// Frequency in Hz, from the mean period.
+    pub fn frequency(&self) -> HALResult<Option<f64>> {
+        Ok(self.period_stats()?.map(|p| 1.0 / p.mean.as_secs_f64()))
+    }
+
+    /// Mean high time over mean period, from 0 to 1.
+    pub fn duty_cycle(&self) -> HALResult<Option<f64>> {
+        let (Some(width), Some(period)) = (self.pulse_width_stats()?, self.period_stats()?) else {
+            return Ok(None);
+        };
+        Ok(Some((width.mean.as_secs_f64() / period.mean.as_secs_f64()).clamp(0.0, 1.0)))
+    }
+
+    /// Discards all measurements.
+    pub fn reset(&self) {
+        let mut state = self.lock();
+        let window = state.window;
+        *state = State { window, ..Default::default() };
+    }
+
+    fn if_running<T>(&self, f: impl FnOnce(&State) -> Option<T>) -> HALResult<Option<T>> {
+        if self.is_stopped()? {
+            return Ok(None);
+        }
+        Ok(f(&self.lock()))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn us(micros: u64) -> FpgaInstant {
+        FpgaInstant::from_micros(micros)
+    }
+
+    fn state(window: usize) -> State {
+        State { window, ..Default::default() }
+    }
+
+    #[test]
+    fn stats_of_nothing() {
+        assert_eq!(PulseStats::compute(&VecDeque::new()), None);
+    }
+
+    #[test]
+    fn stats() {
+        let stats = PulseStats::compute(&VecDeque::from([MS, 3 * MS])).unwrap();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.mean, 2 * MS);
+        assert_eq!(stats.min, MS);
+        assert_eq!(stats.max, 3 * MS);
+        assert_eq!(stats.jitter, MS);
+    }
+
+    #[test]
+    fn steady_signal_has_no_jitter() {
+        let stats = PulseStats::compute(&VecDeque::from([5 * MS; 4])).unwrap();
+        assert_eq!(stats.mean, 5 * MS);
+        assert_eq!(stats.jitter, Duration::ZERO);
+    }
+
+    #[test]
+    fn widths_and_periods() {
+        let mut state = state(8);
+        state.rising(us(0));
+        state.falling(us(300));
+        state.rising(us(1_000));
+        state.falling(us(1_250));
+        assert_eq!(state.widths, [Duration::from_micros(300), Duration::from_micros(250)]);
+        assert_eq!(state.periods, [MS]);
+        assert_eq!(state.last_edge, Some(us(1_250)));
+    }
+
+    #[test]
+    fn fall_without_rise_is_not_a_width() {
+        let mut state = state(8);
+        state.falling(us(100));
+        state.rising(us(200));
+        // a second fall for the same rise was a missed rising edge
+        state.falling(us(400));
+        state.falling(us(600));
+        assert_eq!(state.widths, [Duration::from_micros(200)]);
+    }
+
+    #[test]
+    fn window_keeps_latest() {
+        let mut state = state(2);
+        for i in 0..4 {
+            state.rising(us(i * (i + 1) * 1_000));
+        }
+        assert_eq!(state.periods, [4 * MS, 6 * MS]);
+    }
+}
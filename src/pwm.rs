@@ -2,7 +2,7 @@ use std::ffi::CStr;
 
 use wpihal_sys::{HAL_CheckPWMChannel, HAL_DigitalHandle, HAL_FreePWMPort, HAL_GetPWMConfigMicroseconds, HAL_GetPWMCycleStartTime, HAL_GetPWMEliminateDeadband, HAL_GetPWMLoopTiming, HAL_GetPWMPosition, HAL_GetPWMPulseTimeMicroseconds, HAL_GetPWMSpeed, HAL_InitializePWMPort, HAL_LatchPWMZero, HAL_PortHandle, HAL_SetPWMAlwaysHighMode, HAL_SetPWMConfigMicroseconds, HAL_SetPWMDisabled, HAL_SetPWMEliminateDeadband, HAL_SetPWMPeriodScale, HAL_SetPWMPosition, HAL_SetPWMPulseTimeMicroseconds, HAL_SetPWMSpeed};
 
-use crate::{error::{allocation_location_ptr, HALResult}, hal_call};
+use crate::{error::{allocation_location_ptr, HALResult}, hal_call, time::FpgaInstant};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub struct PWMConfig {
@@ -97,8 +97,8 @@ impl PWM {
         hal_call!(HAL_GetPWMLoopTiming())
     }
 
-    pub fn get_cycle_start_time() -> HALResult<u64> {
-        hal_call!(HAL_GetPWMCycleStartTime())
+    pub fn get_cycle_start_time() -> HALResult<FpgaInstant> {
+        Ok(FpgaInstant::from_micros(hal_call!(HAL_GetPWMCycleStartTime())?))
     }
 }
 
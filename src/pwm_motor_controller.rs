@@ -0,0 +1,126 @@
+use crate::{error::HALResult, power::get_vin_voltage, pwm::{PWMConfig, PWM}};
+
+/// PWM timings of common motor controllers and servos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PWMProfile {
+    Spark,
+    SparkMax,
+    VictorSPX,
+    TalonSRX,
+    Venom,
+    TalonFX,
+    VictorSP,
+    Talon,
+    /// Standard hobby servo. Commands from -1 to 1 span its full travel.
+    Servo,
+}
+
+impl PWMProfile {
+    /// Pulse widths in microseconds.
+    pub fn config(&self) -> PWMConfig {
+        let (max, deadband_max, center, deadband_min, min) = match self {
+            PWMProfile::Spark | PWMProfile::SparkMax => (2003, 1550, 1500, 1460, 999),
+            PWMProfile::VictorSPX | PWMProfile::TalonSRX | PWMProfile::Venom | PWMProfile::TalonFX | PWMProfile::VictorSP => (2004, 1520, 1500, 1480, 997),
+            PWMProfile::Talon => (2037, 1539, 1513, 1487, 989),
+            PWMProfile::Servo => (2400, 0, 0, 0, 600),
+        };
+        PWMConfig { max, deadband_max, center, deadband_min, min }
+    }
+
+    /// Squelch mask passed to [`PWM::set_period_scale`]: 0 keeps every pulse (5.05 ms period),
+    /// 3 keeps every fourth (20.2 ms), which servos need.
+    pub fn period_scale(&self) -> i32 {
+        match self {
+            PWMProfile::Servo => 3,
+            _ => 0,
+        }
+    }
+
+    /// Whether commands inside the controller's deadband are sent as neutral.
+    pub fn eliminate_deadband(&self) -> bool {
+        !self.is_servo()
+    }
+
+    pub fn is_servo(&self) -> bool {
+        matches!(self, PWMProfile::Servo)
+    }
+}
+
+/// Motor controller (or servo) driven over PWM.
+///
+/// The PWM is configured for the profile on creation, and disabled when this is dropped.
+#[derive(Debug)]
+pub struct PWMMotorController {
+    pwm: PWM,
+    profile: PWMProfile,
+    inverted: bool,
+    output: f64,
+}
+
+impl PWMMotorController {
+    pub fn new(mut pwm: PWM, profile: PWMProfile) -> HALResult<Self> {
+        pwm.set_config(&profile.config())?;
+        pwm.set_period_scale(profile.period_scale())?;
+        pwm.set_eliminate_deadband(profile.eliminate_deadband())?;
+        let mut controller = Self { pwm, profile, inverted: false, output: 0.0 };
+        controller.set(0.0)?;
+        controller.pwm.latch_zero()?;
+        Ok(controller)
+    }
+
+    pub fn profile(&self) -> PWMProfile {
+        self.profile
+    }
+
+    pub fn pwm(&self) -> &PWM {
+        &self.pwm
+    }
+
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    pub fn get_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Sets the output from -1 to 1, before inversion. Values outside that are clamped.
+    pub fn set(&mut self, output: f64) -> HALResult<()> {
+        let output = if output.is_nan() { 0.0 } else { output.clamp(-1.0, 1.0) };
+        let signed = if self.inverted { -output } else { output };
+        if self.profile.is_servo() {
+            self.pwm.set_position((signed + 1.0) / 2.0)?;
+        } else {
+            self.pwm.set_speed(signed)?;
+        }
+        self.output = output;
+        Ok(())
+    }
+
+    /// Sets the output as a voltage, scaled by the current battery voltage so the motor sees
+    /// about the same voltage as the battery sags.
+    pub fn set_voltage(&mut self, volts: f64) -> HALResult<()> {
+        let vin = get_vin_voltage()?;
+        if vin <= 0.0 {
+            return self.set(0.0);
+        }
+        self.set(volts / vin)
+    }
+
+    /// The last output set, before inversion.
+    pub fn get(&self) -> f64 {
+        self.output
+    }
+
+    /// Stops sending pulses until the next [`PWMMotorController::set`].
+    pub fn disable(&mut self) -> HALResult<()> {
+        self.output = 0.0;
+        self.pwm.disable()
+    }
+}
+
+impl Drop for PWMMotorController {
+    fn drop(&mut self) {
+        self.pwm.disable().ok();
+    }
+}
@@ -0,0 +1,177 @@
+use std::{panic::AssertUnwindSafe, process::{ExitCode, Termination}, sync::{Arc, Mutex, PoisonError}, time::Duration};
+
+use crate::{driver_station::{get_control_word, observe_user_program_autonomous, observe_user_program_disabled, observe_user_program_starting, observe_user_program_teleop, observe_user_program_test, refresh_ds_data, ControlWord}, error::{send_error, HALResult}, extensions::run_shutdown_hooks, get_fpga_time, initialize, main_loop::run_with_main, notifier::Notifier, shutdown, time::next_deadline, usage_reporting::{report, Language, ResourceType}, HALInitializationMode};
+
+/// Default loop period, same as WPILib's TimedRobot.
+pub const DEFAULT_PERIOD: Duration = Duration::from_millis(20);
+
+/// Process exit code used by [`start_robot`] when the HAL fails to initialize.
+pub const HAL_INIT_FAILED_EXIT_CODE: u8 = 255;
+
+/// Notifier pacing the loop in [`run`], if one is running, so [`end_competition`] can stop it.
+static RUNNING_LOOP: Mutex<Option<Arc<Notifier>>> = Mutex::new(None);
+
+/// Which mode the robot is being driven in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotMode {
+    Disabled,
+    Autonomous,
+    Teleop,
+    Test,
+}
+
+impl From<ControlWord> for RobotMode {
+    fn from(word: ControlWord) -> Self {
+        if !word.enabled() {
+            RobotMode::Disabled
+        } else if word.test() {
+            RobotMode::Test
+        } else if word.autonomous() {
+            RobotMode::Autonomous
+        } else {
+            RobotMode::Teleop
+        }
+    }
+}
+
+/// A TimedRobot-style robot, driven by [`run`].
+///
+/// Every cycle, the current mode's `*_periodic` is called, followed by [`Robot::robot_periodic`].
+/// The mode's `*_init` is called once on the first cycle of a new mode.
+pub trait Robot: Sized {
+    /// Constructs the robot. Runs before the DS is told that robot code is ready.
+    fn robot_init() -> HALResult<Self>;
+
+    fn robot_periodic(&mut self) {}
+
+    fn disabled_init(&mut self) {}
+    fn disabled_periodic(&mut self) {}
+
+    fn autonomous_init(&mut self) {}
+    fn autonomous_periodic(&mut self) {}
+
+    fn teleop_init(&mut self) {}
+    fn teleop_periodic(&mut self) {}
+
+    fn test_init(&mut self) {}
+    fn test_periodic(&mut self) {}
+}
+
+/// Runs a [`Robot`] every `period` until [`end_competition`] is called, then returns `Ok(())`.
+///
+/// The loop is paced by a single notifier using absolute alarm times, so it doesn't drift.
+/// If a cycle overruns, the missed cycles are skipped rather than run back to back.
+/// Returns early if a HAL call the loop depends on fails.
+pub fn run<R: Robot>(period: Duration) -> HALResult<()> {
+    let mut robot = R::robot_init()?;
+
+    let mut notifier = Notifier::initialize()?;
+    notifier.set_name(c"Robot")?;
+    let notifier = Arc::new(notifier);
+    *RUNNING_LOOP.lock().unwrap_or_else(PoisonError::into_inner) = Some(notifier.clone());
+    let result = run_loop(&mut robot, &notifier, period);
+    RUNNING_LOOP.lock().unwrap_or_else(PoisonError::into_inner).take();
+    result
+}
+
+/// Stops the loop started by [`run`] after the current cycle, like WPILib's `endCompetition`.
+/// Does nothing if no loop is running.
+///
+/// Can be called from the robot's own methods or from any other thread.
+pub fn end_competition() -> HALResult<()> {
+    match RUNNING_LOOP.lock().unwrap_or_else(PoisonError::into_inner).as_ref() {
+        Some(notifier) => notifier.stop(),
+        None => Ok(()),
+    }
+}
+
+fn run_loop<R: Robot>(robot: &mut R, notifier: &Notifier, period: Duration) -> HALResult<()> {
+    observe_user_program_starting();
+
+    let mut expiration = get_fpga_time()? + period;
+    let mut mode = None;
+
+    loop {
+        notifier.update_alarm(expiration)?;
+        if notifier.wait_for_alarm()?.is_none() {
+            // stopped by end_competition
+            break;
+        }
+
+        refresh_ds_data();
+        let next = RobotMode::from(get_control_word()?);
+        if mode != Some(next) {
+            match next {
+                RobotMode::Disabled => robot.disabled_init(),
+                RobotMode::Autonomous => robot.autonomous_init(),
+                RobotMode::Teleop => robot.teleop_init(),
+                RobotMode::Test => robot.test_init(),
+            }
+            mode = Some(next);
+        }
+
+        match next {
+            RobotMode::Disabled => {
+                observe_user_program_disabled();
+                robot.disabled_periodic();
+            }
+            RobotMode::Autonomous => {
+                observe_user_program_autonomous();
+                robot.autonomous_periodic();
+            }
+            RobotMode::Teleop => {
+                observe_user_program_teleop();
+                robot.teleop_periodic();
+            }
+            RobotMode::Test => {
+                observe_user_program_test();
+                robot.test_periodic();
+            }
+        }
+        robot.robot_periodic();
+
+        expiration = next_deadline(expiration, period, get_fpga_time()?).0;
+    }
+
+    Ok(())
+}
+
+/// Robot program entry point; this is what `#[wpihal::robot_main]` expands to.
+///
+/// Initializes the HAL, reports the Rust language to usage reporting, and runs `robot_main`
+/// through [`run_with_main`]. Once `robot_main` returns or panics, the shutdown hooks are run
+/// and the HAL is shut down.
+///
+/// The DS is not told robot code is ready here: [`run`] does that after [`Robot::robot_init`].
+/// A `robot_main` that doesn't use [`run`] should call [`observe_user_program_starting`] itself
+/// once it has finished initializing.
+///
+/// If the HAL can't be initialized, an error is printed and sent to the DS, and
+/// [`HAL_INIT_FAILED_EXIT_CODE`] is returned without running `robot_main`.
+pub fn start_robot<T: Termination>(robot_main: impl FnOnce() -> T + Send + 'static) -> ExitCode {
+    if !initialize(500, HALInitializationMode::TryKillExisting) {
+        eprintln!("FATAL ERROR: HAL could not be initialized");
+        send_error(-1, c"FATAL ERROR: HAL could not be initialized").ok();
+        return ExitCode::from(HAL_INIT_FAILED_EXIT_CODE);
+    }
+    report(ResourceType::kLanguage, Language::kRust as i32);
+
+    let exit_code = Arc::new(Mutex::new(None));
+    let robot_exit_code = exit_code.clone();
+    let outcome = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        run_with_main(move || {
+            let code = robot_main().report();
+            *robot_exit_code.lock().unwrap() = Some(code);
+        });
+    }));
+
+    // a panic on the robot thread already ran them from the panic hook, if one was installed
+    run_shutdown_hooks();
+    shutdown();
+
+    if let Err(panic) = outcome {
+        std::panic::resume_unwind(panic);
+    }
+    let code = exit_code.lock().unwrap().take();
+    code.unwrap_or(ExitCode::FAILURE)
+}
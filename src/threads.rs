@@ -1,11 +1,12 @@
-
-use std::thread::JoinHandle;
+use std::{io, sync::mpsc, thread::{JoinHandle, Thread}};
 #[cfg(unix)]
-use std::os::unix::thread::JoinHandleExt;
+use std::{collections::HashMap, os::unix::thread::JoinHandleExt, sync::{Mutex, OnceLock, PoisonError}, thread::ThreadId};
 
 #[allow(unused)]
-use wpihal_sys::{HAL_GetCurrentThreadPriority, HAL_GetThreadPriority, HAL_SetCurrentThreadPriority, HAL_SetThreadPriority, NativeThreadHandle};
+use wpihal_sys::{HAL_GetCurrentThreadPriority, HAL_GetThreadPriority, HAL_SetCurrentThreadPriority, HAL_SetThreadPriority, NativeThreadHandle, HAL_HANDLE_ERROR};
 
+#[cfg(unix)]
+use crate::error::HALError;
 use crate::error::HALResult;
 use crate::hal_call;
 
@@ -15,8 +16,115 @@ pub struct ThreadPriority {
     pub real_time: bool,
 }
 
-/// Gets thread priority.
-/// No-op on windows.
+impl ThreadPriority {
+    /// Real-time (SCHED_FIFO) priority; 1 is lowest, 99 is highest.
+    pub const fn real_time(priority: i32) -> Self {
+        Self { priority, real_time: true }
+    }
+
+    /// Regular time-sliced priority.
+    pub const fn normal() -> Self {
+        Self { priority: 0, real_time: false }
+    }
+}
+
+/// Native handles of threads that have registered themselves, keyed by [`ThreadId`].
+///
+/// Entries are removed by a thread-local guard as the thread exits. Priority calls on other
+/// threads are made with the lock held, so a handle can't be used after its thread is gone.
+#[cfg(unix)]
+fn registry() -> &'static Mutex<HashMap<ThreadId, usize>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<ThreadId, usize>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[cfg(unix)]
+struct Registration(ThreadId);
+
+#[cfg(unix)]
+impl Drop for Registration {
+    fn drop(&mut self) {
+        registry().lock().unwrap_or_else(PoisonError::into_inner).remove(&self.0);
+    }
+}
+
+#[cfg(unix)]
+thread_local! {
+    static REGISTRATION: std::cell::RefCell<Option<Registration>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Makes the current thread visible to [`get_registered_thread_priority`] and
+/// [`set_registered_thread_priority`] from other threads.
+///
+/// Threads spawned by [`RtThreadBuilder`] are registered automatically.
+/// The registration is removed when the thread exits.
+#[cfg(unix)]
+pub fn register_current_thread() {
+    REGISTRATION.with(|reg| {
+        let mut reg = reg.borrow_mut();
+        if reg.is_none() {
+            let id = std::thread::current().id();
+            let pthread = unsafe { libc::pthread_self() } as usize;
+            registry().lock().unwrap_or_else(PoisonError::into_inner).insert(id, pthread);
+            *reg = Some(Registration(id));
+        }
+    });
+}
+#[cfg(not(unix))]
+pub fn register_current_thread() {}
+
+/// Runs `f` with the native handle of `thread`, while the thread is guaranteed to still be alive.
+///
+/// Fails with `HAL_HANDLE_ERROR` if `thread` is neither the current thread nor registered.
+#[cfg(unix)]
+fn with_native_handle<R>(thread: &Thread, f: impl FnOnce(NativeThreadHandle) -> HALResult<R>) -> HALResult<R> {
+    if thread.id() == std::thread::current().id() {
+        return f(unsafe { libc::pthread_self() } as NativeThreadHandle);
+    }
+    let registry = registry().lock().unwrap_or_else(PoisonError::into_inner);
+    match registry.get(&thread.id()) {
+        Some(&pthread) => f(pthread as NativeThreadHandle),
+        None => Err(HALError(HAL_HANDLE_ERROR)),
+    }
+}
+
+/// Gets the priority of a thread by its [`Thread`], for when there is no [`JoinHandle`] at hand
+/// (e.g. from inside another thread).
+///
+/// `thread` must be the current thread, or have been spawned by [`RtThreadBuilder`]
+/// or registered with [`register_current_thread`]; any other thread, including ones spawned
+/// internally by this crate, fails with `HAL_HANDLE_ERROR`.
+/// Always returns a normal priority on non-unix platforms.
+#[cfg(unix)]
+pub fn get_registered_thread_priority(thread: &Thread) -> HALResult<ThreadPriority> {
+    with_native_handle(thread, |handle| {
+        let mut is_real_time: i32 = 0;
+        let priority = hal_call!(HAL_GetThreadPriority(handle, &mut is_real_time))?;
+        Ok(ThreadPriority { priority, real_time: is_real_time != 0 })
+    })
+}
+#[cfg(not(unix))]
+pub fn get_registered_thread_priority(_thread: &Thread) -> HALResult<ThreadPriority> {
+    Ok(ThreadPriority::normal())
+}
+
+/// Sets the priority of a thread by its [`Thread`], returning whether it was applied.
+///
+/// The same restrictions as [`get_registered_thread_priority`] apply.
+/// Always returns false on non-unix platforms.
+#[cfg(unix)]
+pub fn set_registered_thread_priority(thread: &Thread, priority: ThreadPriority) -> HALResult<bool> {
+    with_native_handle(thread, |handle| {
+        Ok(hal_call!(HAL_SetThreadPriority(handle, priority.real_time as i32, priority.priority))? != 0)
+    })
+}
+#[cfg(not(unix))]
+pub fn set_registered_thread_priority(_thread: &Thread, _priority: ThreadPriority) -> HALResult<bool> {
+    Ok(false)
+}
+
+/// Gets the priority of a thread through its join handle.
+/// Always returns a normal priority on non-unix platforms.
 #[cfg(unix)]
 pub fn get_thread_priority<T>(handle: &JoinHandle<T>) -> HALResult<ThreadPriority> {
     let pthread_t = handle.as_pthread_t() as NativeThreadHandle;
@@ -27,7 +135,19 @@ pub fn get_thread_priority<T>(handle: &JoinHandle<T>) -> HALResult<ThreadPriorit
 }
 #[cfg(not(unix))]
 pub fn get_thread_priority<T>(_handle: &JoinHandle<T>) -> HALResult<ThreadPriority> {
-    Ok(ThreadPriority { priority: 0, real_time: false })
+    Ok(ThreadPriority::normal())
+}
+
+/// Sets the priority of a thread through its join handle.
+/// Always returns false on non-unix platforms.
+#[cfg(unix)]
+pub fn set_thread_priority<T>(handle: &JoinHandle<T>, priority: ThreadPriority) -> HALResult<bool> {
+    let pthread_t = handle.as_pthread_t() as NativeThreadHandle;
+    Ok(hal_call!(HAL_SetThreadPriority(pthread_t, priority.real_time as i32, priority.priority))? != 0)
+}
+#[cfg(not(unix))]
+pub fn set_thread_priority<T>(_handle: &JoinHandle<T>, _priority: ThreadPriority) -> HALResult<bool> {
+    Ok(false)
 }
 
 pub fn get_current_thread_priority() -> HALResult<ThreadPriority> {
@@ -37,19 +157,101 @@ pub fn get_current_thread_priority() -> HALResult<ThreadPriority> {
     Ok(ThreadPriority { priority, real_time: is_real_time != 0 })
 }
 
-/// Sets thread priority.
-/// No-op on windows.
-#[cfg(unix)]
-pub fn set_thread_priority<T>(handle: &JoinHandle<T>, priority: ThreadPriority) -> HALResult<bool> {
-    let pthread_t = handle.as_pthread_t() as NativeThreadHandle;
-    Ok(hal_call!(HAL_SetThreadPriority(pthread_t, priority.real_time as i32, priority.priority))? != 0)
+pub fn set_current_thread_priority(priority: ThreadPriority) -> HALResult<bool> {
+    Ok(hal_call!(HAL_SetCurrentThreadPriority(priority.real_time as i32, priority.priority))? != 0)
 }
 
-#[cfg(not(unix))]
-pub fn set_thread_priority<T>(_handle: &JoinHandle<T>) -> HALResult<bool> {
-    Ok(true)
+/// Pins the current thread to one CPU core. Returns false if that failed or isn't supported.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(core: usize) -> bool {
+    if core >= libc::CPU_SETSIZE as usize {
+        return false;
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_core: usize) -> bool {
+    false
 }
 
-pub fn set_current_thread_priority(priority: ThreadPriority) -> HALResult<bool> {
-    Ok(hal_call!(HAL_SetCurrentThreadPriority(priority.real_time as i32, priority.priority))? != 0)
-}
\ No newline at end of file
+/// What a thread spawned by [`RtThreadBuilder`] actually got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RtThreadStatus {
+    /// Whether the requested priority was applied. True if no priority was requested.
+    pub priority_granted: bool,
+    /// Whether the thread was pinned to the requested core. True if no core was requested.
+    pub pinned: bool,
+}
+
+/// Spawns a named thread that has its priority and CPU affinity set before the closure runs.
+///
+/// Real-time priorities generally need the process to run as the admin user on the roboRIO
+/// and are refused elsewhere; [`RtThreadBuilder::spawn`] reports whether they were granted
+/// rather than failing.
+///
+/// ```ignore
+/// let (handle, status) = RtThreadBuilder::new("control")
+///     .priority(ThreadPriority::real_time(40))
+///     .cpu(1)
+///     .spawn(|| control_loop())?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct RtThreadBuilder {
+    name: String,
+    priority: Option<ThreadPriority>,
+    cpu: Option<usize>,
+    stack_size: Option<usize>,
+}
+
+impl RtThreadBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), priority: None, cpu: None, stack_size: None }
+    }
+
+    pub fn priority(mut self, priority: ThreadPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Pins the thread to one CPU core (Linux only).
+    pub fn cpu(mut self, core: usize) -> Self {
+        self.cpu = Some(core);
+        self
+    }
+
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// Spawns the thread, waiting until its priority and affinity have been set.
+    pub fn spawn<F, T>(self, f: F) -> io::Result<(JoinHandle<T>, RtThreadStatus)>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut builder = std::thread::Builder::new().name(self.name);
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+
+        let (tx, rx) = mpsc::sync_channel(1);
+        let (priority, cpu) = (self.priority, self.cpu);
+        let handle = builder.spawn(move || {
+            register_current_thread();
+            let status = RtThreadStatus {
+                priority_granted: priority.map(|p| set_current_thread_priority(p).unwrap_or(false)).unwrap_or(true),
+                pinned: cpu.map(pin_current_thread).unwrap_or(true),
+            };
+            tx.send(status).ok();
+            f()
+        })?;
+
+        let status = rx.recv().unwrap_or_default();
+        Ok((handle, status))
+    }
+}
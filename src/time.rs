@@ -0,0 +1,194 @@
+use core::{fmt, ops::{Add, AddAssign, Sub, SubAssign}};
+use std::time::Duration;
+
+use crate::{can_api::get_can_packet_base_time, error::HALResult, expand_fpga_time, get_fpga_time};
+
+/// A point in time on the FPGA clock, in microseconds since the FPGA started.
+///
+/// Everything in the HAL that hands back a timestamp on this clock returns one of these,
+/// so microseconds, milliseconds and 32-bit truncated values can't be mixed up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct FpgaInstant(u64);
+
+impl FpgaInstant {
+    pub const fn from_micros(micros: u64) -> Self {
+        Self(micros)
+    }
+
+    pub const fn as_micros(&self) -> u64 {
+        self.0
+    }
+
+    /// The current FPGA time.
+    pub fn now() -> HALResult<Self> {
+        get_fpga_time()
+    }
+
+    /// Expands a timestamp holding only the lower 32 bits of the FPGA time.
+    pub fn from_lower_32(lower: u32) -> HALResult<Self> {
+        expand_fpga_time(lower)
+    }
+
+    /// Converts the 32-bit millisecond timestamp on a CAN message (e.g. [`crate::can::CANStreamMessage`]'s
+    /// `timeStamp`) using [`get_can_packet_base_time`] as the reference.
+    ///
+    /// The result only has millisecond resolution, and is only correct for messages less than
+    /// about 49 days old.
+    pub fn from_can_timestamp(timestamp_ms: u32) -> HALResult<Self> {
+        let base_ms = get_can_packet_base_time();
+        let now = get_fpga_time()?;
+        let age_ms = base_ms.wrapping_sub(timestamp_ms) as u64;
+        Ok(Self(now.0.saturating_sub(age_ms * 1000)))
+    }
+
+    /// Time since the FPGA started.
+    pub const fn since_start(&self) -> Duration {
+        Duration::from_micros(self.0)
+    }
+
+    /// Time between `earlier` and this instant, or zero if `earlier` is later.
+    pub fn saturating_duration_since(&self, earlier: FpgaInstant) -> Duration {
+        Duration::from_micros(self.0.saturating_sub(earlier.0))
+    }
+
+    pub fn checked_duration_since(&self, earlier: FpgaInstant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(Duration::from_micros)
+    }
+
+    /// Time elapsed since this instant.
+    pub fn elapsed(&self) -> HALResult<Duration> {
+        Ok(get_fpga_time()?.saturating_duration_since(*self))
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Self> {
+        self.0.checked_add(u64::try_from(duration.as_micros()).ok()?).map(Self)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Self> {
+        self.0.checked_sub(u64::try_from(duration.as_micros()).ok()?).map(Self)
+    }
+}
+
+impl Add<Duration> for FpgaInstant {
+    type Output = FpgaInstant;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        self.checked_add(rhs).expect("overflow when adding duration to FpgaInstant")
+    }
+}
+
+impl AddAssign<Duration> for FpgaInstant {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub<Duration> for FpgaInstant {
+    type Output = FpgaInstant;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        self.checked_sub(rhs).expect("overflow when subtracting duration from FpgaInstant")
+    }
+}
+
+impl SubAssign<Duration> for FpgaInstant {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = *self - rhs;
+    }
+}
+
+/// Saturates to zero, like [`std::time::Instant`].
+impl Sub<FpgaInstant> for FpgaInstant {
+    type Output = Duration;
+
+    fn sub(self, rhs: FpgaInstant) -> Self::Output {
+        self.saturating_duration_since(rhs)
+    }
+}
+
+impl fmt::Display for FpgaInstant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.6}s", self.0 as f64 / 1e6)
+    }
+}
+
+/// Given a deadline that was just serviced, returns the next deadline one `period` later,
+/// pushed forward past `now` if the caller fell behind, along with the number of whole periods skipped.
+pub(crate) fn next_deadline(serviced: FpgaInstant, period: Duration, now: FpgaInstant) -> (FpgaInstant, u64) {
+    let period_us = (period.as_micros() as u64).max(1);
+    let next = serviced.0 + period_us;
+    if now.0 >= next {
+        let skipped = (now.0 - next) / period_us + 1;
+        (FpgaInstant(next + skipped * period_us), skipped)
+    } else {
+        (FpgaInstant(next), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn at(micros: u64) -> FpgaInstant {
+        FpgaInstant::from_micros(micros)
+    }
+
+    #[test]
+    fn duration_arithmetic() {
+        let mut t = at(1_000);
+        assert_eq!(t + MS, at(2_000));
+        assert_eq!(t - MS, at(0));
+        t += 2 * MS;
+        assert_eq!(t, at(3_000));
+        t -= MS;
+        assert_eq!(t, at(2_000));
+        assert_eq!(t.since_start(), 2 * MS);
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        assert_eq!(at(500).checked_sub(MS), None);
+        assert_eq!(at(u64::MAX).checked_add(Duration::from_micros(1)), None);
+        assert_eq!(at(u64::MAX - 1).checked_add(Duration::from_micros(1)), Some(at(u64::MAX)));
+        assert_eq!(at(0).checked_add(Duration::MAX), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sub_duration_overflow_panics() {
+        let _ = at(500) - MS;
+    }
+
+    #[test]
+    fn instant_difference_saturates() {
+        assert_eq!(at(3_000) - at(1_000), 2 * MS);
+        assert_eq!(at(1_000) - at(3_000), Duration::ZERO);
+        assert_eq!(at(1_000).checked_duration_since(at(3_000)), None);
+        assert_eq!(at(3_000).checked_duration_since(at(1_000)), Some(2 * MS));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(at(1_500_000).to_string(), "1.500000s");
+    }
+
+    #[test]
+    fn next_deadline_on_time() {
+        assert_eq!(next_deadline(at(20_000), 20 * MS, at(25_000)), (at(40_000), 0));
+    }
+
+    #[test]
+    fn next_deadline_skips_missed_periods() {
+        // landing exactly on the next deadline counts as missing it
+        assert_eq!(next_deadline(at(20_000), 20 * MS, at(40_000)), (at(60_000), 1));
+        assert_eq!(next_deadline(at(20_000), 20 * MS, at(85_000)), (at(100_000), 3));
+    }
+
+    #[test]
+    fn next_deadline_zero_period() {
+        let (next, _) = next_deadline(at(1_000), Duration::ZERO, at(1_000));
+        assert!(next > at(1_000));
+    }
+}
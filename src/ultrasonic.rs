@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use crate::{analog_input::AnalogInput, counter::{Counter, SemiPeriod}, dio::{DigitalSource, DIO}, error::HALResult, get_fpga_time, time::FpgaInstant};
+
+/// Metres per inch, for sensors specified in inches.
+const METRES_PER_INCH: f64 = 0.0254;
+
+/// Ping/echo ultrasonic range finder, such as the HC-SR04 or Devantech SRF04.
+///
+/// A short pulse on the ping output starts a measurement, and the sensor holds the echo input high
+/// for as long as the sound took to come back, which is measured by a semi-period [`Counter`].
+///
+/// Pinging several sensors at once makes them hear each other, so to use more than one,
+/// hand them to an [`UltrasonicRoundRobin`].
+#[derive(Debug)]
+pub struct Ultrasonic<'a> {
+    ping: DIO,
+    counter: Counter<'a, SemiPeriod>,
+    speed_of_sound: f64,
+}
+
+impl<'a> Ultrasonic<'a> {
+    /// Length of the trigger pulse, in seconds.
+    pub const PING_TIME: f64 = 10e-6;
+    /// Speed of sound in dry air at 20 °C, in metres per second.
+    pub const SPEED_OF_SOUND: f64 = 343.0;
+
+    /// * `ping` - DIO initialized as an output, pulsed to trigger a measurement
+    /// * `echo` - DIO initialized as an input, connected to the echo pin
+    pub fn new(ping: DIO, echo: &'a DIO) -> HALResult<Self> {
+        let mut counter = Counter::semi_period(DigitalSource::DigitalInput(echo), true)?;
+        counter.set_max_period(1.0)?;
+        Ok(Self { ping, counter, speed_of_sound: Self::SPEED_OF_SOUND })
+    }
+
+    /// Sets the speed of sound used to turn echo times into ranges, in metres per second.
+    pub fn set_speed_of_sound(&mut self, speed_of_sound: f64) {
+        self.speed_of_sound = speed_of_sound;
+    }
+
+    /// Starts a measurement, throwing away the previous one.
+    pub fn ping(&mut self) -> HALResult<()> {
+        self.counter.reset()?;
+        self.ping.pulse(Self::PING_TIME)
+    }
+
+    /// Whether an echo has been timed since the last ping.
+    pub fn is_range_valid(&self) -> HALResult<bool> {
+        Ok(self.counter.get()? > 1)
+    }
+
+    /// Range to the target in metres, or `None` if no echo has been timed since the last ping.
+    pub fn get_range(&self) -> HALResult<Option<f64>> {
+        if !self.is_range_valid()? {
+            return Ok(None);
+        }
+        // the echo time covers the trip there and back
+        Ok(Some(self.counter.get_period()? * self.speed_of_sound / 2.0))
+    }
+}
+
+/// Pings a set of [`Ultrasonic`] sensors one at a time, so they don't pick up each other's echoes.
+///
+/// Call [`UltrasonicRoundRobin::update`] every loop; it pings the next sensor once the previous
+/// one has had its interval to hear back. Each sensor is therefore updated once every
+/// `interval` times the number of sensors.
+#[derive(Debug)]
+pub struct UltrasonicRoundRobin<'a> {
+    sensors: Vec<Ultrasonic<'a>>,
+    interval: Duration,
+    next: usize,
+    last_ping: Option<FpgaInstant>,
+}
+
+impl<'a> UltrasonicRoundRobin<'a> {
+    /// Time allowed for an echo before the next sensor is pinged,
+    /// enough for about 17 m there and back.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn new(sensors: Vec<Ultrasonic<'a>>) -> Self {
+        Self { sensors, interval: Self::DEFAULT_INTERVAL, next: 0, last_ping: None }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Pings the next sensor if the previous one's interval is up.
+    pub fn update(&mut self) -> HALResult<()> {
+        if self.sensors.is_empty() {
+            return Ok(());
+        }
+        let now = get_fpga_time()?;
+        if self.last_ping.is_some_and(|last| now - last < self.interval) {
+            return Ok(());
+        }
+        self.sensors[self.next].ping()?;
+        self.last_ping = Some(now);
+        self.next = (self.next + 1) % self.sensors.len();
+        Ok(())
+    }
+
+    /// The sensors, in the order they were given.
+    pub fn sensors(&self) -> &[Ultrasonic<'a>] {
+        &self.sensors
+    }
+
+    /// Range from sensor `index` in metres, or `None` if it has no valid reading.
+    pub fn get_range(&self, index: usize) -> HALResult<Option<f64>> {
+        match self.sensors.get(index) {
+            Some(sensor) => sensor.get_range(),
+            None => Ok(None),
+        }
+    }
+
+    pub fn into_sensors(self) -> Vec<Ultrasonic<'a>> {
+        self.sensors
+    }
+}
+
+/// Ultrasonic range finder with an analog output proportional to range, such as the MaxBotix
+/// MB10xx series.
+#[derive(Debug)]
+pub struct AnalogUltrasonic<'a> {
+    input: &'a AnalogInput,
+    volts_per_inch: f64,
+    min_range: f64,
+    max_range: f64,
+}
+
+impl<'a> AnalogUltrasonic<'a> {
+    /// Scale of the MaxBotix LV-MaxSonar-EZ and HRLV-MaxSonar-EZ at 5 V (Vcc/512 per inch).
+    pub const MAXBOTIX_VOLTS_PER_INCH: f64 = 5.0 / 512.0;
+
+    pub fn new(input: &'a AnalogInput, volts_per_inch: f64) -> Self {
+        Self { input, volts_per_inch, min_range: 0.0, max_range: f64::INFINITY }
+    }
+
+    /// Sets the ranges, in metres, the sensor can actually measure. Readings outside them are
+    /// treated as invalid. By default anything above zero is accepted.
+    pub fn set_valid_range(&mut self, min: f64, max: f64) {
+        self.min_range = min.max(0.0);
+        self.max_range = max.max(self.min_range);
+    }
+
+    /// Range to the target in metres, or `None` if the reading is outside the valid range
+    /// (including a disconnected sensor reading 0 V).
+    pub fn get_range(&self) -> HALResult<Option<f64>> {
+        if self.volts_per_inch <= 0.0 {
+            return Ok(None);
+        }
+        let range = self.input.get_average_voltage()? / self.volts_per_inch * METRES_PER_INCH;
+        let valid = range > 0.0 && range >= self.min_range && range <= self.max_range;
+        Ok(valid.then_some(range))
+    }
+}
@@ -0,0 +1,181 @@
+use std::{ffi::CString, fmt::Write, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread::JoinHandle, time::Duration};
+
+use crate::{error::{send_warning, thread_spawn_error, HALResult}, get_fpga_time, notifier::Notifier, time::FpgaInstant};
+
+/// Min/avg/max loop timing collected by a [`LoopWatchdog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LoopStats {
+    /// Number of completed cycles.
+    pub count: u64,
+    /// Number of cycles that ran past the timeout.
+    pub overruns: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+}
+
+/// Loop-overrun watchdog, a la WPILib's Watchdog and Tracer.
+///
+/// Call [`LoopWatchdog::reset`] at the start of each cycle, [`LoopWatchdog::epoch`] after each
+/// section of interest, and [`LoopWatchdog::disable`] at the end of the cycle.
+/// If the timeout passes before `disable`, the callback fires from a background thread.
+/// Once the cycle ends, the per-epoch breakdown of an overrun cycle is sent to the DS as a warning,
+/// at most once per print period.
+pub struct LoopWatchdog {
+    timeout: Duration,
+    notifier: Arc<Notifier>,
+    expired: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    armed: bool,
+    start: FpgaInstant,
+    last_epoch: FpgaInstant,
+    epochs: Vec<(&'static str, Duration)>,
+    print_period: Duration,
+    last_print: Option<FpgaInstant>,
+    total: Duration,
+    stats: LoopStats,
+}
+
+impl LoopWatchdog {
+    /// Creates a disarmed watchdog.
+    ///
+    /// * `timeout` - how long a cycle may take before it counts as an overrun
+    /// * `callback` - called from the watchdog thread when a cycle overruns
+    pub fn new(timeout: Duration, mut callback: impl FnMut() + Send + 'static) -> HALResult<Self> {
+        let mut notifier = Notifier::initialize()?;
+        notifier.set_name(c"LoopWatchdog")?;
+        let notifier = Arc::new(notifier);
+        let expired = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let notifier = notifier.clone();
+            let expired = expired.clone();
+            std::thread::Builder::new().name("LoopWatchdog".to_string()).spawn(move || {
+                while let Ok(Some(_)) = notifier.wait_for_alarm() {
+                    expired.store(true, Ordering::Release);
+                    callback();
+                }
+            }).map_err(|e| thread_spawn_error("LoopWatchdog", e))?
+        };
+
+        Ok(Self {
+            timeout,
+            notifier,
+            expired,
+            thread: Some(thread),
+            armed: false,
+            start: FpgaInstant::default(),
+            last_epoch: FpgaInstant::default(),
+            epochs: Vec::new(),
+            print_period: Duration::from_secs(1),
+            last_print: None,
+            total: Duration::ZERO,
+            stats: LoopStats::default(),
+        })
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the minimum time between overrun warnings. Defaults to one second.
+    pub fn set_print_period(&mut self, period: Duration) {
+        self.print_period = period;
+    }
+
+    /// Arms the watchdog for a new cycle and clears the recorded epochs.
+    pub fn reset(&mut self) -> HALResult<()> {
+        let now = get_fpga_time()?;
+        self.start = now;
+        self.last_epoch = now;
+        self.epochs.clear();
+        self.expired.store(false, Ordering::Release);
+        self.notifier.update_alarm(now + self.timeout)?;
+        self.armed = true;
+        Ok(())
+    }
+
+    /// Records the time since the previous epoch (or [`LoopWatchdog::reset`]) under `name`.
+    pub fn epoch(&mut self, name: &'static str) {
+        let Ok(now) = get_fpga_time() else { return; };
+        self.epochs.push((name, now - self.last_epoch));
+        self.last_epoch = now;
+    }
+
+    /// Ends the cycle: disarms the alarm, updates the loop statistics,
+    /// and prints the epoch breakdown if the cycle overran.
+    pub fn disable(&mut self) -> HALResult<()> {
+        if !self.armed {
+            return Ok(());
+        }
+        self.notifier.cancel_alarm()?;
+        self.armed = false;
+
+        let now = get_fpga_time()?;
+        let elapsed = now - self.start;
+        let overran = elapsed > self.timeout || self.expired.load(Ordering::Acquire);
+        self.record(elapsed, overran);
+
+        let can_print = match self.last_print {
+            Some(last) => now - last >= self.print_period,
+            None => true,
+        };
+        if overran && can_print {
+            self.last_print = Some(now);
+            self.print_epochs(elapsed);
+        }
+        Ok(())
+    }
+
+    /// Whether the current cycle has already run past the timeout.
+    pub fn is_expired(&self) -> bool {
+        self.expired.load(Ordering::Acquire)
+    }
+
+    pub fn stats(&self) -> LoopStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = LoopStats::default();
+        self.total = Duration::ZERO;
+    }
+
+    fn record(&mut self, elapsed: Duration, overran: bool) {
+        let stats = &mut self.stats;
+        if stats.count == 0 {
+            stats.min = elapsed;
+            stats.max = elapsed;
+        } else {
+            stats.min = stats.min.min(elapsed);
+            stats.max = stats.max.max(elapsed);
+        }
+        stats.count += 1;
+        stats.overruns += overran as u64;
+        self.total += elapsed;
+        stats.mean = Duration::from_nanos((self.total.as_nanos() / stats.count as u128) as u64);
+    }
+
+    fn print_epochs(&self, elapsed: Duration) {
+        let mut msg = format!(
+            "Loop time of {:.6}s overrun ({:.6}s)",
+            self.timeout.as_secs_f64(),
+            elapsed.as_secs_f64()
+        );
+        for (name, time) in self.epochs.iter() {
+            write!(msg, "\n\t{}: {:.6}s", name, time.as_secs_f64()).ok();
+        }
+        if let Ok(msg) = CString::new(msg) {
+            send_warning(1, &msg).ok();
+        }
+    }
+}
+
+impl Drop for LoopWatchdog {
+    fn drop(&mut self) {
+        self.notifier.stop().ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
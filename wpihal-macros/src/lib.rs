@@ -0,0 +1,147 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Fields, Ident, Index, ItemFn, Member};
+
+/// Turns a function into a robot program entry point.
+///
+/// The generated function initializes the HAL, reports the Rust language to usage reporting,
+/// runs the annotated function (on a worker thread if a simulation extension owns the main loop),
+/// and shuts the HAL down afterwards. The annotated function is responsible for telling the DS
+/// robot code is ready, which `wpihal::robot::run` does after robot init.
+/// See `wpihal::robot::start_robot` for the details.
+///
+/// The annotated function must take no arguments and may return anything implementing
+/// `std::process::Termination`.
+///
+/// ```ignore
+/// #[wpihal::robot_main]
+/// fn main() {
+///     wpihal::robot::run::<MyRobot>(wpihal::robot::DEFAULT_PERIOD).unwrap();
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn robot_main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(proc_macro2::TokenStream::from(attr).span(), "robot_main takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let func = parse_macro_input!(item as ItemFn);
+    let sig = &func.sig;
+    if let Some(asyncness) = &sig.asyncness {
+        return syn::Error::new(asyncness.span(), "robot_main cannot be async").to_compile_error().into();
+    }
+    if let Some(unsafety) = &sig.unsafety {
+        return syn::Error::new(unsafety.span(), "robot_main cannot be unsafe").to_compile_error().into();
+    }
+    if let Some(constness) = &sig.constness {
+        return syn::Error::new(constness.span(), "robot_main cannot be const").to_compile_error().into();
+    }
+    if let Some(abi) = &sig.abi {
+        return syn::Error::new(abi.span(), "robot_main cannot have an extern ABI").to_compile_error().into();
+    }
+    if !sig.inputs.is_empty() {
+        return syn::Error::new(sig.inputs.span(), "robot_main functions cannot take arguments").to_compile_error().into();
+    }
+    if !sig.generics.params.is_empty() {
+        return syn::Error::new(sig.generics.span(), "robot_main functions cannot be generic").to_compile_error().into();
+    }
+
+    let attrs = &func.attrs;
+    let vis = &func.vis;
+    let name = &sig.ident;
+    let output = &sig.output;
+    let block = &func.block;
+
+    quote! {
+        #(#attrs)*
+        #vis fn #name() -> ::std::process::ExitCode {
+            fn __wpihal_robot_main() #output #block
+            ::wpihal::robot::start_robot(__wpihal_robot_main)
+        }
+    }
+    .into()
+}
+
+/// Implements `wpihal::dma::DMARecord` for a struct, so DMA samples can be decoded into it.
+///
+/// Every field needs a `#[dma(...)]` attribute naming what it holds:
+///
+/// * `encoder`, `encoder_period`, `counter`, `counter_period`, `analog`, `averaged_analog`,
+///   `duty_cycle` - an `i32` raw value
+/// * `digital` - a `bool`
+/// * `accumulator` - an `(i64, i64)` of (count, value)
+/// * `timestamp` - a `wpihal::time::FpgaInstant` with the sample time; this doesn't take a channel
+///
+/// The remaining fields map, in order, to the channels added to the `DMABuilder`.
+#[proc_macro_derive(DMASample, attributes(dma))]
+pub fn derive_dma_sample(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match dma_record_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn dma_record_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(input.ident.span(), "DMASample can only be derived for structs"));
+    };
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(input.generics.span(), "DMASample structs cannot be generic"));
+    }
+    let fields = match &data.fields {
+        Fields::Named(fields) => &fields.named,
+        Fields::Unnamed(fields) => &fields.unnamed,
+        Fields::Unit => return Err(syn::Error::new(input.ident.span(), "DMASample structs need at least one field")),
+    };
+
+    let mut kinds = Vec::new();
+    let mut inits = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        let Some(attr) = field.attrs.iter().find(|attr| attr.path().is_ident("dma")) else {
+            return Err(syn::Error::new(field.span(), "missing #[dma(...)] attribute"));
+        };
+        let kind: Ident = attr.parse_args()?;
+
+        if kind == "timestamp" {
+            inits.push(quote! { #member: sample.get_sample_time()? });
+            continue;
+        }
+        let (variant, read) = match kind.to_string().as_str() {
+            "encoder" => ("Encoder", "read_raw"),
+            "encoder_period" => ("EncoderPeriod", "read_raw"),
+            "counter" => ("Counter", "read_raw"),
+            "counter_period" => ("CounterPeriod", "read_raw"),
+            "digital" => ("DigitalSource", "read_digital"),
+            "analog" => ("AnalogInput", "read_raw"),
+            "averaged_analog" => ("AveragedAnalogInput", "read_raw"),
+            "accumulator" => ("AnalogAccumulator", "read_accumulator"),
+            "duty_cycle" => ("DutyCycle", "read_raw"),
+            _ => return Err(syn::Error::new(kind.span(), "unknown DMA channel kind")),
+        };
+        let variant = Ident::new(variant, kind.span());
+        let read = Ident::new(read, kind.span());
+        let channel = kinds.len();
+        kinds.push(quote! { ::wpihal::dma::DMAChannelKind::#variant });
+        inits.push(quote! { #member: channels[#channel].#read(sample)? });
+    }
+
+    let name = &input.ident;
+    Ok(quote! {
+        impl ::wpihal::dma::DMARecord for #name {
+            const CHANNELS: &'static [::wpihal::dma::DMAChannelKind] = &[#(#kinds),*];
+
+            // a record of only a timestamp never reads a channel
+            #[allow(unused_variables)]
+            fn from_sample(sample: &::wpihal::dma::DMASample, channels: &[::wpihal::dma::DMAChannel]) -> ::wpihal::error::HALResult<Self> {
+                Ok(Self { #(#inits),* })
+            }
+        }
+    })
+}